@@ -0,0 +1,230 @@
+//! Times `parse` on generated string-, number- and object-heavy documents.
+//!
+//! Run with `cargo bench`; pass a name such as `objects` to run only the
+//! documents whose name contains it. Each document is parsed several times
+//! and the best time is reported, along with the throughput and the time
+//! it takes to drop the parsed value. For the numbers, the time per literal
+//! is compared with converting the same literals with `str::parse::<f64>`.
+
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+const RUNS: usize = 9;
+
+/// A small deterministic generator, so that every run parses the same text.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        self.0 >> 33
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
+
+const WORDS: &[&str] = &[
+    "request",
+    "handled",
+    "in",
+    "ms",
+    "user",
+    "session",
+    "expired",
+    "cache",
+    "miss",
+    "for",
+    "key",
+    "retrying",
+    "upstream",
+    "timeout",
+    "connection",
+    "reset",
+    "by",
+    "peer",
+    "ok",
+];
+
+fn sentence(rng: &mut Lcg, out: &mut String) {
+    for i in 0..4 + rng.below(20) {
+        if i > 0 {
+            out.push(' ');
+        }
+        out.push_str(WORDS[rng.below(WORDS.len() as u64) as usize]);
+    }
+}
+
+/// Log lines, a few of them with escapes or non-ASCII text.
+fn strings(rng: &mut Lcg, count: usize) -> String {
+    let mut out = String::from("[");
+    for i in 0..count {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push('"');
+        sentence(rng, &mut out);
+        match rng.below(10) {
+            0 => out.push_str(r#" \"quoted\"\n"#),
+            1 => out.push_str(" caf\u{e9} \u{1f600}"),
+            _ => {}
+        }
+        out.push('"');
+    }
+    out.push(']');
+    out
+}
+
+/// Rows of sensor readings, mostly floats with a few integers.
+fn numbers(rng: &mut Lcg, count: usize) -> String {
+    let mut out = String::from("[");
+    for i in 0..count {
+        out.push_str(if i > 0 { ",\n[" } else { "[" });
+        for j in 0..8 {
+            if j > 0 {
+                out.push(',');
+            }
+            let n = rng.next();
+            match n % 8 {
+                0 => write!(out, "{}", n % 100_000).unwrap(),
+                1 => write!(out, "-{}.{:06}e-{}", n % 10, n % 1_000_000, n % 20).unwrap(),
+                _ => write!(out, "{}.{:04}", (n % 2_000) as i64 - 1_000, n % 10_000).unwrap(),
+            }
+        }
+        out.push(']');
+    }
+    out.push(']');
+    out
+}
+
+/// Floats like `-1.2345e-12`, all of the same shape, with exponents from
+/// `-min_exp` to `-min_exp - 9`.
+fn exponents(rng: &mut Lcg, count: usize, min_exp: u64) -> String {
+    let mut out = String::from("[");
+    for i in 0..count {
+        if i > 0 {
+            out.push(',');
+        }
+        let n = rng.next();
+        write!(
+            out,
+            "-{}.{:04}e-{}",
+            1 + n % 9,
+            n % 10_000,
+            min_exp + n % 10
+        )
+        .unwrap();
+    }
+    out.push(']');
+    out
+}
+
+/// A pretty-printed array of records, like an API response or a log dump.
+fn objects(rng: &mut Lcg, count: usize) -> String {
+    let mut out = String::from("[\n");
+    for i in 0..count {
+        if i > 0 {
+            out.push_str(",\n");
+        }
+        let mut message = String::new();
+        sentence(rng, &mut message);
+        write!(
+            out,
+            "  {{\n    \"id\": {},\n    \"name\": \"user_{}\",\n    \"active\": {},\n    \
+             \"score\": {}.{},\n    \"tags\": [\"t{}\", \"t{}\"],\n    \"message\": \"{}\",\n    \
+             \"meta\": {{\n      \"region\": \"eu-{}\",\n      \"retries\": {},\n      \
+             \"parent\": null\n    }}\n  }}",
+            i,
+            rng.below(100_000),
+            rng.below(2) == 0,
+            rng.below(100),
+            rng.below(100),
+            rng.below(50),
+            rng.below(50),
+            message,
+            rng.below(4),
+            rng.below(5),
+        )
+        .unwrap();
+    }
+    out.push_str("\n]\n");
+    out
+}
+
+fn best_of<F: FnMut() -> Duration>(mut f: F) -> Duration {
+    (0..RUNS).map(|_| f()).min().unwrap()
+}
+
+fn main() {
+    let filter = std::env::args()
+        .skip(1)
+        .find(|arg| !arg.starts_with('-'))
+        .unwrap_or_default();
+    let mut rng = Lcg(0x5eed);
+    let docs = [
+        ("strings", strings(&mut rng, 250_000)),
+        ("numbers", numbers(&mut rng, 250_000)),
+        ("objects", objects(&mut rng, 100_000)),
+        // the same literals but for the exponent, which puts those of the
+        // second document beyond the fast path for floats
+        ("exp-fast", exponents(&mut rng, 1_000_000, 10)),
+        ("exp-slow", exponents(&mut rng, 1_000_000, 30)),
+    ];
+
+    for (name, src) in &docs {
+        if !name.contains(filter.as_str()) {
+            continue;
+        }
+        // the value is dropped outside the timed part, and timed apart
+        let parse = best_of(|| {
+            let start = Instant::now();
+            let val = json_rs::parse(src).unwrap();
+            let time = start.elapsed();
+            drop(val);
+            time
+        });
+        let drop = best_of(|| {
+            let val = json_rs::parse(src).unwrap();
+            let start = Instant::now();
+            drop(val);
+            start.elapsed()
+        });
+        let mb = src.len() as f64 / 1e6;
+        println!(
+            "{:<8} {:>6.1} MB  parse {:>7.1} ms {:>7.1} MB/s  drop {:>6.1} ms",
+            name,
+            mb,
+            parse.as_secs_f64() * 1e3,
+            mb / parse.as_secs_f64(),
+            drop.as_secs_f64() * 1e3,
+        );
+
+        if *name == "numbers" {
+            // what converting the same literals with `str::parse` costs,
+            // before any tokenizing, validation or tree building
+            let literals = src
+                .split(&['[', ']', ',', '\n'][..])
+                .filter(|s| !s.is_empty())
+                .collect::<Vec<_>>();
+            let str_parse = best_of(|| {
+                let start = Instant::now();
+                for lit in &literals {
+                    std::hint::black_box(lit.parse::<f64>().unwrap());
+                }
+                start.elapsed()
+            });
+            let per_literal = |time: Duration| time.as_secs_f64() * 1e9 / literals.len() as f64;
+            println!(
+                "{:<8} {:>6} literals  parse {:>5.1} ns each  str::parse alone {:>5.1} ns each",
+                "",
+                literals.len(),
+                per_literal(parse),
+                per_literal(str_parse),
+            );
+        }
+    }
+}
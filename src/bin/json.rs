@@ -1,15 +1,77 @@
-use std::io::Read;
+use std::fs::File;
+use std::io::{self, Read};
 
-fn main() -> std::io::Result<()> {
-    let mut s = String::new();
-    std::io::stdin().lock().read_to_string(&mut s)?;
+/// How much of stdin `Tee` keeps, well above what the parser reads ahead.
+const KEPT_LEN: usize = 64 * 1024;
 
-    match json_rs::parse(&s) {
+/// Keeps the end of what is read from stdin, which cannot be read again
+/// to show the offending line.
+///
+/// At most `2 * KEPT_LEN` bytes are kept, so that long inputs are not held
+/// in memory.
+struct Tee<R> {
+    inner: R,
+    kept: Vec<u8>,
+    /// The offset in the input of the first byte kept.
+    start: usize,
+}
+
+impl<R: Read> Read for Tee<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.kept.extend_from_slice(&buf[..n]);
+        if self.kept.len() > 2 * KEPT_LEN {
+            // keep the last `KEPT_LEN` bytes from the first line that starts
+            // in their first half, or else from the first char in them
+            let from = self.kept.len() - KEPT_LEN;
+            let half = &self.kept[from..from + KEPT_LEN / 2];
+            let cut = match half.iter().position(|&b| b == b'\n') {
+                Some(i) => from + i + 1,
+                None => {
+                    let rest = self.kept[from..].iter();
+                    from + rest.take_while(|&&b| b & 0xC0 == 0x80).count()
+                }
+            };
+            self.kept.drain(..cut);
+            self.start += cut;
+        }
+        Ok(n)
+    }
+}
+
+fn main() -> io::Result<()> {
+    let path = std::env::args_os().nth(1);
+    let mut stdin = Tee {
+        inner: io::stdin().lock(),
+        kept: Vec::new(),
+        start: 0,
+    };
+    let res = match path {
+        Some(ref path) => json_rs::parse_reader(File::open(path)?),
+        None => json_rs::parse_reader(&mut stdin),
+    };
+
+    match res {
         Ok(val) => {
             println!("{:?}", val);
         }
         Err(err) => {
-            eprintln!("{:?}", err);
+            // a file is read again rather than kept while parsing
+            let src = match path {
+                Some(path) => std::fs::read(path).ok().map(|bytes| (bytes, 0)),
+                None => Some((stdin.kept, stdin.start)),
+            };
+            match src {
+                Some((bytes, start)) => {
+                    // the error is never past the first invalid UTF-8 byte
+                    let valid = match std::str::from_utf8(&bytes) {
+                        Ok(s) => s,
+                        Err(e) => std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap(),
+                    };
+                    eprintln!("{}", err.render_from(valid, start));
+                }
+                None => eprintln!("error: {}", err),
+            }
         }
     };
     Ok(())
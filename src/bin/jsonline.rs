@@ -13,7 +13,7 @@ fn main() {
         println!("{:?}", res);
 
         if let Ok(val) = res {
-            println!("{}", val.to_string());
+            println!("{}", val);
         }
 
         s.clear();
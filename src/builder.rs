@@ -0,0 +1,372 @@
+use crate::number::Number;
+use crate::options::DuplicateKeyPolicy;
+use crate::types::{JsonMap, JsonValue};
+use crate::value_ref::{JsonMapRef, JsonValueRef};
+
+use indexmap::map::Entry;
+use indexmap::IndexMap;
+use std::borrow::{Borrow, Cow};
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// Receives the values of a document as the parser reads them, in order,
+/// to build any representation of it, see `parse_into`.
+///
+/// Arrays and objects are bracketed by their `begin_*` and `end_*` calls,
+/// and each member value is preceded by a call to `key`. Strings and keys
+/// borrow from the source text unless they contain escapes or the input
+/// is read in chunks.
+///
+/// The parser checks the syntax, the limits and `DuplicateKeyPolicy::Reject`;
+/// how other duplicate keys are handled is up to the builder.
+pub trait JsonBuilder<'a> {
+    type Output;
+
+    fn null(&mut self);
+    fn boolean(&mut self, b: bool);
+    fn number(&mut self, n: Number);
+    fn string(&mut self, s: Cow<'a, str>);
+    /// A string holding lone surrogates, encoded as WTF-8.
+    ///
+    /// Only called when parsing with `SurrogatePolicy::Preserve`.
+    fn wtf8_string(&mut self, bytes: Vec<u8>);
+    fn begin_array(&mut self);
+    fn end_array(&mut self);
+    fn begin_object(&mut self);
+    fn key(&mut self, k: Cow<'a, str>);
+    fn end_object(&mut self);
+
+    /// Returns the result once the whole document has been parsed.
+    fn finish(self) -> Self::Output;
+}
+
+/// The builder of `JsonValue`, used by `parse`.
+pub struct JsonValueBuilder {
+    inner: TreeBuilder<String, JsonValue>,
+}
+
+impl JsonValueBuilder {
+    /// Creates a builder that handles duplicate keys like `policy`.
+    pub fn new(policy: DuplicateKeyPolicy) -> Self {
+        Self {
+            inner: TreeBuilder::new(policy),
+        }
+    }
+}
+
+impl Default for JsonValueBuilder {
+    fn default() -> Self {
+        Self::new(DuplicateKeyPolicy::default())
+    }
+}
+
+impl<'a> JsonBuilder<'a> for JsonValueBuilder {
+    type Output = JsonValue;
+
+    fn null(&mut self) {
+        self.inner.null();
+    }
+
+    fn boolean(&mut self, b: bool) {
+        self.inner.boolean(b);
+    }
+
+    fn number(&mut self, n: Number) {
+        self.inner.number(n);
+    }
+
+    fn string(&mut self, s: Cow<'a, str>) {
+        self.inner.string(s);
+    }
+
+    fn wtf8_string(&mut self, bytes: Vec<u8>) {
+        self.inner.wtf8_string(bytes);
+    }
+
+    fn begin_array(&mut self) {
+        self.inner.begin_array();
+    }
+
+    fn end_array(&mut self) {
+        self.inner.end_array();
+    }
+
+    fn begin_object(&mut self) {
+        self.inner.begin_object();
+    }
+
+    fn key(&mut self, k: Cow<'a, str>) {
+        self.inner.key(k);
+    }
+
+    fn end_object(&mut self) {
+        self.inner.end_object();
+    }
+
+    fn finish(self) -> JsonValue {
+        self.inner.finish()
+    }
+}
+
+/// A value type built by `TreeBuilder`, either `JsonValue` or
+/// `JsonValueRef`.
+pub(crate) trait Tree<'a>: Sized {
+    type Key: Eq + Hash + Clone + Borrow<str>;
+
+    fn null() -> Self;
+    fn boolean(b: bool) -> Self;
+    fn number(n: Number) -> Self;
+    fn string(s: Cow<'a, str>) -> Self;
+    fn wtf8_string(bytes: Vec<u8>) -> Self;
+    fn array(arr: Vec<Self>) -> Self;
+    fn object(map: IndexMap<Self::Key, Self>) -> Self;
+    fn key(k: Cow<'a, str>) -> Self::Key;
+    fn as_array_mut(&mut self) -> Option<&mut Vec<Self>>;
+}
+
+impl<'a> Tree<'a> for JsonValue {
+    type Key = String;
+
+    fn null() -> Self {
+        JsonValue::Null
+    }
+
+    fn boolean(b: bool) -> Self {
+        JsonValue::Boolean(b)
+    }
+
+    fn number(n: Number) -> Self {
+        JsonValue::Number(n)
+    }
+
+    fn string(s: Cow<'a, str>) -> Self {
+        JsonValue::String(s.into_owned())
+    }
+
+    fn wtf8_string(bytes: Vec<u8>) -> Self {
+        JsonValue::Wtf8String(bytes)
+    }
+
+    fn array(arr: Vec<Self>) -> Self {
+        JsonValue::Array(arr)
+    }
+
+    fn object(map: JsonMap) -> Self {
+        JsonValue::Object(map)
+    }
+
+    fn key(k: Cow<'a, str>) -> String {
+        k.into_owned()
+    }
+
+    fn as_array_mut(&mut self) -> Option<&mut Vec<Self>> {
+        match self {
+            JsonValue::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Tree<'a> for JsonValueRef<'a> {
+    type Key = Cow<'a, str>;
+
+    fn null() -> Self {
+        JsonValueRef::Null
+    }
+
+    fn boolean(b: bool) -> Self {
+        JsonValueRef::Boolean(b)
+    }
+
+    fn number(n: Number) -> Self {
+        JsonValueRef::Number(n)
+    }
+
+    fn string(s: Cow<'a, str>) -> Self {
+        JsonValueRef::String(s)
+    }
+
+    fn wtf8_string(bytes: Vec<u8>) -> Self {
+        JsonValueRef::Wtf8String(bytes)
+    }
+
+    fn array(arr: Vec<Self>) -> Self {
+        JsonValueRef::Array(arr)
+    }
+
+    fn object(map: JsonMapRef<'a>) -> Self {
+        JsonValueRef::Object(map)
+    }
+
+    fn key(k: Cow<'a, str>) -> Cow<'a, str> {
+        k
+    }
+
+    fn as_array_mut(&mut self) -> Option<&mut Vec<Self>> {
+        match self {
+            JsonValueRef::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+}
+
+/// An array or object whose end has not been reached yet, with the
+/// offset in `TreeBuilder` of its first child.
+enum Open<K> {
+    Array(usize),
+    /// `key` is the key of the member whose value comes next.
+    Object {
+        start: usize,
+        key: Option<K>,
+    },
+}
+
+/// Builds a tree of values on a heap-allocated stack, so that nesting is
+/// bounded only by memory.
+///
+/// The children of the open containers are kept on two shared stacks
+/// until their container ends, so that each array or map is allocated
+/// once, at its final size.
+pub(crate) struct TreeBuilder<K, V> {
+    stack: Vec<Open<K>>,
+    elements: Vec<V>,
+    members: Vec<(K, V)>,
+    root: Option<V>,
+    policy: DuplicateKeyPolicy,
+}
+
+impl<K, V> TreeBuilder<K, V> {
+    pub(crate) fn new(policy: DuplicateKeyPolicy) -> Self {
+        Self {
+            stack: Vec::new(),
+            elements: Vec::new(),
+            members: Vec::new(),
+            root: None,
+            policy,
+        }
+    }
+}
+
+impl<'a, V: Tree<'a>> TreeBuilder<V::Key, V> {
+    /// Adds a finished value to the innermost container.
+    fn push(&mut self, v: V) {
+        match self.stack.last_mut() {
+            None => self.root = Some(v),
+            Some(Open::Array(_)) => self.elements.push(v),
+            Some(Open::Object { key, .. }) => {
+                let k = key.take().expect("object member without key");
+                self.members.push((k, v));
+            }
+        }
+    }
+
+    /// Collects the members from `start` on into a map, handling duplicate
+    /// keys like the policy says.
+    fn take_members(&mut self, start: usize) -> IndexMap<V::Key, V> {
+        let mut map = IndexMap::with_capacity(self.members.len() - start);
+        let mut collected = HashSet::new();
+        for (k, v) in self.members.drain(start..) {
+            let mut entry = match map.entry(k) {
+                Entry::Vacant(entry) => {
+                    entry.insert(v);
+                    continue;
+                }
+                Entry::Occupied(entry) => entry,
+            };
+            match self.policy {
+                DuplicateKeyPolicy::Reject | DuplicateKeyPolicy::KeepLast => {
+                    entry.insert(v);
+                }
+                DuplicateKeyPolicy::KeepFirst => {}
+                DuplicateKeyPolicy::CollectAll => {
+                    let key: &str = entry.key().borrow();
+                    if collected.contains(key) {
+                        if let Some(arr) = entry.get_mut().as_array_mut() {
+                            arr.push(v);
+                        }
+                    } else {
+                        let first = std::mem::replace(entry.get_mut(), V::null());
+                        entry.insert(V::array(vec![first, v]));
+                        collected.insert(entry.key().clone());
+                    }
+                }
+            }
+        }
+        map
+    }
+}
+
+impl<'a, V: Tree<'a>> JsonBuilder<'a> for TreeBuilder<V::Key, V> {
+    type Output = V;
+
+    fn null(&mut self) {
+        self.push(V::null());
+    }
+
+    fn boolean(&mut self, b: bool) {
+        self.push(V::boolean(b));
+    }
+
+    fn number(&mut self, n: Number) {
+        self.push(V::number(n));
+    }
+
+    fn string(&mut self, s: Cow<'a, str>) {
+        self.push(V::string(s));
+    }
+
+    fn wtf8_string(&mut self, bytes: Vec<u8>) {
+        self.push(V::wtf8_string(bytes));
+    }
+
+    fn begin_array(&mut self) {
+        self.stack.push(Open::Array(self.elements.len()));
+    }
+
+    fn end_array(&mut self) {
+        match self.stack.pop() {
+            Some(Open::Array(start)) => {
+                let arr = match start {
+                    // hand over the whole stack instead of copying it, as
+                    // for a large array at the root
+                    0 => {
+                        let mut arr = std::mem::take(&mut self.elements);
+                        arr.shrink_to_fit();
+                        arr
+                    }
+                    _ => self.elements.drain(start..).collect(),
+                };
+                self.push(V::array(arr));
+            }
+            _ => panic!("end of array without a matching begin"),
+        }
+    }
+
+    fn begin_object(&mut self) {
+        self.stack.push(Open::Object {
+            start: self.members.len(),
+            key: None,
+        });
+    }
+
+    fn key(&mut self, k: Cow<'a, str>) {
+        match self.stack.last_mut() {
+            Some(Open::Object { key, .. }) => *key = Some(V::key(k)),
+            _ => panic!("key outside of an object"),
+        }
+    }
+
+    fn end_object(&mut self) {
+        match self.stack.pop() {
+            Some(Open::Object { start, .. }) => {
+                let map = self.take_members(start);
+                self.push(V::object(map));
+            }
+            _ => panic!("end of object without a matching begin"),
+        }
+    }
+
+    fn finish(self) -> V {
+        self.root.expect("no value has been built")
+    }
+}
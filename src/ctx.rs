@@ -1,233 +1,1932 @@
-use crate::types::{is_unescaped_char, is_whitespace};
-use crate::types::{JsonError, JsonResult, JsonValue};
-use crate::validate::validate_number;
+use crate::builder::JsonBuilder;
+use crate::error::{JsonError, JsonErrorKind, JsonResult, Position};
+use crate::events::JsonEvent;
+use crate::number::{conversion_loss, Number};
+use crate::options::{
+    Dialect, DuplicateKeyPolicy, LossyNumberPolicy, ParseOptions, SurrogatePolicy,
+};
+use crate::reader::{ReadError, ReadInput, Utf8Chunks};
+use crate::types::{
+    is_identifier_part, is_identifier_start, is_json5_whitespace, is_whitespace, unescaped_len,
+};
+use crate::validate::{scan_json5_number, scan_number, validate_number, NumberScan};
 
-use std::collections::HashMap;
-use std::iter::Peekable;
-use std::str::Chars;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::io::Read;
+
+const EXPECT_VALUE: &[&str] = &["value"];
+const EXPECT_VALUE_OR_BRACKET: &[&str] = &["value", "']'"];
+const EXPECT_KEY: &[&str] = &["string"];
+const EXPECT_KEY_OR_BRACE: &[&str] = &["string", "'}'"];
+const EXPECT_COLON: &[&str] = &["':'"];
+const EXPECT_COMMA_OR_BRACKET: &[&str] = &["','", "']'"];
+const EXPECT_COMMA_OR_BRACE: &[&str] = &["','", "'}'"];
+const EXPECT_VALUE_END: &[&str] = &["','", "']'", "'}'", "whitespace"];
+const EXPECT_STRING_END: &[&str] = &["'\"'"];
+const EXPECT_SINGLE_QUOTE_END: &[&str] = &["\"'\""];
+const EXPECT_ESCAPE: &[&str] = &[
+    "'\"'", "'\\'", "'/'", "'b'", "'f'", "'n'", "'r'", "'t'", "'u'",
+];
+const EXPECT_IDENTIFIER_ESCAPE: &[&str] = &["'u'"];
+const EXPECT_HEX_DIGIT: &[&str] = &["hex digit"];
+const EXPECT_NULL: &[&str] = &["null"];
+const EXPECT_TRUE: &[&str] = &["true"];
+const EXPECT_FALSE: &[&str] = &["false"];
+const EXPECT_INFINITY: &[&str] = &["Infinity"];
+const EXPECT_POS_INFINITY: &[&str] = &["+Infinity"];
+const EXPECT_NEG_INFINITY: &[&str] = &["-Infinity"];
+const EXPECT_NAN: &[&str] = &["NaN"];
+const EXPECT_POS_NAN: &[&str] = &["+NaN"];
+const EXPECT_NEG_NAN: &[&str] = &["-NaN"];
+const EXPECT_COMMENT: &[&str] = &["'/'", "'*'"];
+const EXPECT_COMMENT_END: &[&str] = &["'*/'"];
+
+/// The members of an object being parsed.
+struct Members {
+    /// Number of members read so far, including duplicates.
+    count: usize,
+    /// The keys read so far, only kept for `DuplicateKeyPolicy::Reject`.
+    keys: Option<HashSet<String>>,
+}
+
+impl Members {
+    fn new(policy: DuplicateKeyPolicy) -> Self {
+        Self {
+            count: 0,
+            keys: match policy {
+                DuplicateKeyPolicy::Reject => Some(HashSet::new()),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// An array or object whose closing bracket has not been reached yet.
+enum Open {
+    /// Holds the number of elements so far.
+    Array(usize),
+    Object(Members),
+}
+
+/// What `EventState` parses next.
+///
+/// A step either completes or fails before changing anything, so that it
+/// can be run again once more input has been pushed. Tokens cut off by
+/// the end of the input are kept in `JsonContext::partial` instead.
+enum EventStep {
+    /// A value comes next.
+    Value,
+    /// The first element of the innermost array, or its end, comes next.
+    FirstElement,
+    /// The first member of the innermost object, or its end, comes next.
+    FirstMember,
+    /// The key of a member of the innermost object comes next.
+    Key,
+    /// The colon after the key on top of the path comes next.
+    Colon,
+    /// A value has ended; the char right after it comes next.
+    ValueEnd,
+    /// What follows a value and its whitespace comes next.
+    AfterValue,
+    /// An element of the innermost array comes next, after a comma.
+    AfterComma,
+    Done,
+}
+
+/// The state of `JsonEvents` and `JsonPushParser` between two events.
+pub(crate) struct EventState {
+    stack: Vec<Open>,
+    step: EventStep,
+    /// The first byte of the scalar being parsed, for tracing its end.
+    scalar: u8,
+}
+
+impl EventState {
+    pub(crate) fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            step: EventStep::Value,
+            scalar: 0,
+        }
+    }
+
+    /// Parses up to the end of the next event, returning `None` once the
+    /// document has ended or an error has been returned, or when pushed
+    /// input has run out before the next event.
+    pub(crate) fn next_event(&mut self, ctx: &mut JsonContext) -> JsonResult<Option<JsonEvent>> {
+        if let Some(err) = ctx.take_input_error() {
+            self.step = EventStep::Done;
+            return Err(err);
+        }
+        let res = self.step(ctx);
+        if let Ok(Some(_)) = res {
+            return res;
+        }
+        if let Some(err) = ctx.take_input_error() {
+            self.step = EventStep::Done;
+            return Err(err);
+        }
+        if res.is_err() && ctx.starved {
+            return Ok(None);
+        }
+        self.step = EventStep::Done;
+        res
+    }
+
+    fn step(&mut self, ctx: &mut JsonContext) -> JsonResult<Option<JsonEvent>> {
+        loop {
+            match self.step {
+                EventStep::Done => return Ok(None),
+                EventStep::Value => {
+                    let mut scalar = ScalarEvent(None);
+                    match ctx.partial.take() {
+                        Some(partial) => ctx.resume_scalar(partial, &mut scalar)?,
+                        None => {
+                            ctx.parse_whitespace()?;
+                            let b = ctx.peek_or_end(EXPECT_VALUE)?;
+                            ctx.trace_value_start(b);
+                            match b {
+                                b'[' => {
+                                    ctx.enter_nested()?;
+                                    ctx.consume();
+                                    self.stack.push(Open::Array(0));
+                                    self.step = EventStep::FirstElement;
+                                    return Ok(Some(JsonEvent::StartArray));
+                                }
+                                b'{' => {
+                                    ctx.enter_nested()?;
+                                    ctx.consume();
+                                    let members = Members::new(ctx.opts.duplicate_keys);
+                                    self.stack.push(Open::Object(members));
+                                    self.step = EventStep::FirstMember;
+                                    return Ok(Some(JsonEvent::StartObject));
+                                }
+                                b => {
+                                    self.scalar = b;
+                                    ctx.parse_scalar(b, &mut scalar)?
+                                }
+                            }
+                        }
+                    }
+                    ctx.trace_value_end(self.scalar);
+                    self.step = EventStep::ValueEnd;
+                    return Ok(scalar.0);
+                }
+                EventStep::FirstElement => {
+                    ctx.parse_whitespace()?;
+                    if ctx.peek_or_end(EXPECT_VALUE_OR_BRACKET)? == b']' {
+                        return Ok(Some(self.close(ctx)));
+                    }
+                    ctx.push_index(0)?;
+                    self.step = EventStep::Value;
+                }
+                EventStep::FirstMember => {
+                    ctx.parse_whitespace()?;
+                    if ctx.peek_or_end(EXPECT_KEY_OR_BRACE)? == b'}' {
+                        return Ok(Some(self.close(ctx)));
+                    }
+                    self.step = EventStep::Key;
+                }
+                EventStep::Key => {
+                    let members = match self.stack.last_mut() {
+                        Some(Open::Object(members)) => members,
+                        _ => unreachable!(),
+                    };
+                    let (start, buf) = match ctx.partial.take() {
+                        Some(Partial::String(state)) => {
+                            (state.start, ctx.parse_string_rest(state)?)
+                        }
+                        Some(Partial::Identifier(state)) => {
+                            (state.start, ctx.parse_identifier(state)?)
+                        }
+                        Some(_) => unreachable!(),
+                        None => {
+                            if !ctx.parse_key_start(members)? {
+                                return Ok(Some(self.close(ctx)));
+                            }
+                            (ctx.offset, ctx.parse_key_raw()?)
+                        }
+                    };
+                    ctx.push_member_key(start, buf, members)?;
+                    self.step = EventStep::Colon;
+                }
+                EventStep::Colon => {
+                    ctx.parse_colon()?;
+                    self.step = EventStep::Value;
+                    return Ok(Some(JsonEvent::Key(ctx.member_key().into_owned())));
+                }
+                EventStep::ValueEnd => {
+                    ctx.await_input()?;
+                    ctx.check_value_end()?;
+                    self.step = EventStep::AfterValue;
+                }
+                EventStep::AfterValue => {
+                    ctx.parse_whitespace()?;
+                    ctx.await_input()?;
+                    match self.stack.last_mut() {
+                        None => {
+                            if ctx.peek().is_some() {
+                                return Err(ctx.error(JsonErrorKind::RootNotSingular));
+                            }
+                            self.step = EventStep::Done;
+                            return Ok(None);
+                        }
+                        Some(Open::Array(len)) => {
+                            ctx.path.pop();
+                            *len += 1;
+                            match ctx.peek_or_end(EXPECT_COMMA_OR_BRACKET)? {
+                                b',' => {
+                                    ctx.consume();
+                                    self.step = EventStep::AfterComma;
+                                }
+                                b']' => return Ok(Some(self.close(ctx))),
+                                _ => {
+                                    return Err(ctx.error_expecting(
+                                        JsonErrorKind::ExpectedCommaOrBracket,
+                                        EXPECT_COMMA_OR_BRACKET,
+                                    ))
+                                }
+                            }
+                        }
+                        Some(Open::Object(members)) => {
+                            ctx.pop_member_key();
+                            members.count += 1;
+                            match ctx.peek_or_end(EXPECT_COMMA_OR_BRACE)? {
+                                b',' => {
+                                    ctx.consume();
+                                    self.step = EventStep::Key;
+                                }
+                                b'}' => return Ok(Some(self.close(ctx))),
+                                _ => {
+                                    return Err(ctx.error_expecting(
+                                        JsonErrorKind::ExpectedCommaOrBrace,
+                                        EXPECT_COMMA_OR_BRACE,
+                                    ))
+                                }
+                            }
+                        }
+                    }
+                }
+                EventStep::AfterComma => {
+                    ctx.parse_whitespace()?;
+                    ctx.await_input()?;
+                    if let Some(b']') = ctx.peek() {
+                        if ctx.opts.dialect.trailing_commas() {
+                            return Ok(Some(self.close(ctx)));
+                        }
+                        return Err(ctx.error(JsonErrorKind::TrailingComma));
+                    }
+                    let len = match self.stack.last() {
+                        Some(Open::Array(len)) => *len,
+                        _ => unreachable!(),
+                    };
+                    ctx.push_index(len)?;
+                    self.step = EventStep::Value;
+                }
+            }
+        }
+    }
+
+    /// Consumes the bracket that closes the innermost container.
+    fn close(&mut self, ctx: &mut JsonContext) -> JsonEvent {
+        ctx.consume();
+        ctx.leave_nested();
+        self.step = EventStep::ValueEnd;
+        match self.stack.pop() {
+            Some(Open::Array(_)) => {
+                ctx.trace_value_end(b'[');
+                JsonEvent::EndArray
+            }
+            Some(Open::Object(_)) => {
+                ctx.trace_value_end(b'{');
+                JsonEvent::EndObject
+            }
+            None => unreachable!(),
+        }
+    }
+}
+
+/// Receives the single scalar parsed by a step of `EventState`.
+struct ScalarEvent(Option<JsonEvent>);
+
+impl<'a> JsonBuilder<'a> for ScalarEvent {
+    type Output = Option<JsonEvent>;
+
+    fn null(&mut self) {
+        self.0 = Some(JsonEvent::Null);
+    }
+
+    fn boolean(&mut self, b: bool) {
+        self.0 = Some(JsonEvent::Boolean(b));
+    }
+
+    fn number(&mut self, n: Number) {
+        self.0 = Some(JsonEvent::Number(n));
+    }
+
+    fn string(&mut self, s: Cow<'a, str>) {
+        self.0 = Some(JsonEvent::String(s.into_owned()));
+    }
+
+    fn wtf8_string(&mut self, bytes: Vec<u8>) {
+        self.0 = Some(JsonEvent::Wtf8String(bytes));
+    }
+
+    fn begin_array(&mut self) {
+        unreachable!()
+    }
+
+    fn end_array(&mut self) {
+        unreachable!()
+    }
+
+    fn begin_object(&mut self) {
+        unreachable!()
+    }
+
+    fn key(&mut self, _: Cow<'a, str>) {
+        unreachable!()
+    }
+
+    fn end_object(&mut self) {
+        unreachable!()
+    }
+
+    fn finish(self) -> Option<JsonEvent> {
+        self.0
+    }
+}
+
+/// Names the kind of value that starts with `b`, for trace events.
+fn value_kind(b: u8) -> Option<&'static str> {
+    Some(match b {
+        b'[' => "array",
+        b'{' => "object",
+        b'"' | b'\'' => "string",
+        b't' | b'f' => "boolean",
+        b'n' => "null",
+        b'-' | b'0'..=b'9' | b'+' | b'.' | b'I' | b'N' => "number",
+        _ => return None,
+    })
+}
+
+enum PathSegment<'a> {
+    Index(usize),
+    Key(Cow<'a, str>),
+}
+
+/// Decoded string contents, which become WTF-8 once a lone surrogate has
+/// been preserved. Borrowed from the source text while it has no escapes
+/// and `src` is borrowed.
+struct StrBuf<'a> {
+    bytes: Cow<'a, [u8]>,
+    wtf8: bool,
+}
+
+impl<'a> StrBuf<'a> {
+    fn new() -> Self {
+        Self {
+            bytes: Cow::Borrowed(&[]),
+            wtf8: false,
+        }
+    }
+
+    fn push(&mut self, ch: char) {
+        let mut tmp = [0; 4];
+        self.bytes
+            .to_mut()
+            .extend_from_slice(ch.encode_utf8(&mut tmp).as_bytes());
+    }
+
+    /// Appends a run of source text, which is whole chars because runs end
+    /// only at ASCII bytes.
+    fn push_run(&mut self, run: &[u8]) {
+        self.bytes.to_mut().extend_from_slice(run);
+    }
+
+    fn push_surrogate(&mut self, unit: u16) {
+        self.bytes.to_mut().extend_from_slice(&[
+            0xE0 | (unit >> 12) as u8,
+            0x80 | ((unit >> 6) & 0x3F) as u8,
+            0x80 | (unit & 0x3F) as u8,
+        ]);
+        self.wtf8 = true;
+    }
+
+    /// The last char pushed, unless a lone surrogate has been preserved.
+    fn last_char(&self) -> Option<char> {
+        if self.wtf8 {
+            return None;
+        }
+        // only whole chars have been pushed into a buffer without surrogates
+        unsafe { std::str::from_utf8_unchecked(&self.bytes) }
+            .chars()
+            .next_back()
+    }
+
+    /// Returns the contents as text, or as WTF-8 bytes if a lone surrogate
+    /// has been preserved.
+    fn into_text(self) -> Result<Cow<'a, str>, Vec<u8>> {
+        if self.wtf8 {
+            return Err(self.bytes.into_owned());
+        }
+        // only whole chars have been pushed into a buffer without surrogates
+        Ok(match self.bytes {
+            Cow::Borrowed(bytes) => Cow::Borrowed(unsafe { std::str::from_utf8_unchecked(bytes) }),
+            Cow::Owned(bytes) => Cow::Owned(unsafe { String::from_utf8_unchecked(bytes) }),
+        })
+    }
+}
+
+/// Progress through an escape sequence in a string.
+#[derive(Clone, Copy)]
+enum Escape {
+    None,
+    /// After the backslash at `start`. `high` holds a high surrogate
+    /// escaped at the given offset right before, in case this escape
+    /// turns out to be its low surrogate.
+    Backslash {
+        start: usize,
+        high: Option<(u16, usize)>,
+    },
+    /// Inside the hex digits of a `\u` escape starting at `start`, or of
+    /// a JSON5 `\x` escape, which starts with `digits` at 2.
+    Hex {
+        start: usize,
+        unit: u16,
+        digits: u8,
+        high: Option<(u16, usize)>,
+    },
+    /// After a high surrogate escaped at `start`, which a `\u` escape of a
+    /// low surrogate may follow.
+    High {
+        start: usize,
+        unit: u16,
+    },
+    /// After a JSON5 line continuation ending in `\r`, which may be
+    /// followed by a `\n` that belongs to it.
+    CarriageReturn,
+    /// After a JSON5 `\0` escape, which must not be followed by a digit.
+    Zero,
+}
+
+/// A string being parsed, starting with the quote at `start`, or an
+/// unquoted JSON5 key starting at `start`.
+struct StrState<'a> {
+    start: usize,
+    /// `"`, or `'` in JSON5, or 0 for an unquoted key.
+    quote: u8,
+    buf: StrBuf<'a>,
+    escape: Escape,
+}
+
+/// A token cut off by the end of the input pushed so far, to be finished
+/// once more has arrived.
+enum Partial<'a> {
+    /// `expected[0]` is the literal, of which `matched` bytes have been
+    /// consumed.
+    Literal {
+        expected: &'static [&'static str],
+        matched: usize,
+    },
+    /// A number starting at the current offset, of which `scanned` bytes
+    /// are known to be number chars.
+    Number {
+        scanned: usize,
+    },
+    String(StrState<'a>),
+    /// An unquoted JSON5 object key.
+    Identifier(StrState<'a>),
+}
+
+/// Progress through a comment, kept in `JsonContext::comment` if the input
+/// pushed so far runs out inside it.
+#[derive(Clone, Copy)]
+enum Comment {
+    /// After the opening slash.
+    Slash,
+    Line,
+    /// Inside a block comment, right after a `*` if `star`.
+    Block {
+        star: bool,
+    },
+}
 
 pub struct JsonContext<'a> {
-    chars: Peekable<Chars<'a>>,
+    /// UTF-8 encoded source text, or the part of it read so far that has
+    /// not been discarded when parsing from a reader.
+    src: Cow<'a, [u8]>,
+    /// Byte offset of the next unconsumed byte in `src`, always at a char
+    /// boundary between tokens.
+    offset: usize,
+    /// Offset in the whole input of `src[0]`.
+    base: usize,
+    /// Set while a string is parsed, so that nothing is discarded from
+    /// `src` and the offsets saved for its errors stay valid.
+    pinned: bool,
+    reader: Option<ReadInput<'a>>,
+    /// Checks pushed input until its end is marked, see `feed`.
+    push: Option<Utf8Chunks>,
+    /// Set when the input pushed so far has run out, so that the error
+    /// this causes stands for waiting for more instead.
+    starved: bool,
+    partial: Option<Partial<'a>>,
+    comment: Option<Comment>,
+    /// A read failure, reported instead of whatever the parser made of the
+    /// input ending early.
+    input_error: Option<JsonError>,
+    /// The last position computed by `position_at`, to resume counting
+    /// lines and columns from.
+    checkpoint: Cell<Position>,
+    path: Vec<PathSegment<'a>>,
+    depth: usize,
+    opts: ParseOptions,
+    warnings: Vec<JsonError>,
 }
 
 impl<'a> JsonContext<'a> {
-    pub fn new(src: &'a str) -> Self {
-        let chars = src.chars().peekable();
-        Self { chars }
+    pub fn new(src: &'a str, opts: &ParseOptions) -> Self {
+        let mut ctx = Self::with_input(Cow::Borrowed(src.as_bytes()), None, opts);
+        if let Some(max) = opts.max_input_len {
+            if src.len() > max {
+                ctx.input_error = Some(ctx.error(JsonErrorKind::InputTooLarge));
+            }
+        }
+        ctx
     }
 
-    pub fn peek(&mut self) -> Option<char> {
-        self.chars.peek().cloned()
+    /// Creates a context that reads its input from `reader` as it goes.
+    pub fn from_reader(reader: &'a mut dyn Read, opts: &ParseOptions) -> Self {
+        Self::with_input(Cow::Owned(Vec::new()), Some(ReadInput::new(reader)), opts)
     }
 
-    #[cfg(not(debug_assertions))]
-    fn consume(&mut self) -> Option<char> {
-        self.chars.next()
+    /// Creates a context whose input is pushed in pieces with `feed`.
+    pub fn for_push(opts: &ParseOptions) -> JsonContext<'static> {
+        let mut ctx = JsonContext::with_input(Cow::Owned(Vec::new()), None, opts);
+        ctx.push = Some(Utf8Chunks::new());
+        ctx
     }
 
-    #[cfg(debug_assertions)]
-    fn consume(&mut self) -> Option<char> {
-        self.chars.next().map(|ch| dbg!(ch))
+    fn with_input(src: Cow<'a, [u8]>, reader: Option<ReadInput<'a>>, opts: &ParseOptions) -> Self {
+        Self {
+            src,
+            offset: 0,
+            base: 0,
+            pinned: false,
+            reader,
+            push: None,
+            starved: false,
+            partial: None,
+            comment: None,
+            input_error: None,
+            checkpoint: Cell::new(Position::start()),
+            path: Vec::new(),
+            depth: 0,
+            opts: *opts,
+            warnings: Vec::new(),
+        }
     }
 
-    pub fn parse_value(&mut self) -> JsonResult<JsonValue> {
-        self.parse_whitespace();
-        let ch = self.peek().ok_or(JsonError::UnexpectedEnd)?;
+    /// The warnings recorded so far, see `LossyNumberPolicy::Warn`.
+    pub fn into_warnings(self) -> Vec<JsonError> {
+        self.warnings
+    }
 
-        let val = match ch {
-            'n' => self.parse_null(),
-            't' => self.parse_true(),
-            'f' => self.parse_false(),
-            '"' => self.parse_string(),
-            '[' => self.parse_array(),
-            '{' => self.parse_object(),
-            c if c == '-' || c.is_digit(10) => self.parse_number(),
-            _ => Err(JsonError::InvalidValue),
-        }?;
+    /// Takes the error that stopped reading the input, if any. Input over
+    /// `max_input_len` is reported here before parsing starts.
+    pub fn take_input_error(&mut self) -> Option<JsonError> {
+        self.input_error.take()
+    }
 
-        if let Some(ch) = self.peek() {
-            if !",]}".contains(ch) && !is_whitespace(ch) {
-                return Err(JsonError::InvalidValue);
+    /// Appends a piece of pushed input after the end of `src`, discarding
+    /// what has been consumed.
+    pub fn feed(&mut self, chunk: &[u8]) {
+        self.starved = false;
+        self.discard_consumed();
+        if let Some(ref mut chunks) = self.push {
+            chunks.push(chunk, self.src.to_mut());
+        }
+        match self.opts.max_input_len {
+            Some(max) if self.base + self.src.len() > max && self.input_error.is_none() => {
+                self.input_error =
+                    Some(self.error_at(JsonErrorKind::InputTooLarge, max - self.base, &[]));
             }
+            _ => {}
         }
-
-        self.parse_whitespace();
-        Ok(val)
     }
 
-    fn parse_whitespace(&mut self) {
-        while let Some(ch) = self.peek() {
-            if is_whitespace(ch) {
-                self.consume();
-            } else {
-                break;
+    /// Marks the end of the pushed input.
+    pub fn finish_input(&mut self) {
+        self.starved = false;
+        if let Some(chunks) = self.push.take() {
+            if chunks.finish().is_err() && self.input_error.is_none() {
+                let end = self.src.len();
+                self.input_error = Some(self.error_at(JsonErrorKind::InvalidUtf8, end, &[]));
             }
         }
     }
 
-    #[inline(always)]
-    fn parse_literal(s: &'static str) -> impl Fn(&mut JsonContext) -> JsonResult<()> {
-        move |ctx| {
-            for b in s.chars() {
-                let a = ctx.consume().ok_or(JsonError::UnexpectedEnd)?;
-                if a != b {
-                    return Err(JsonError::InvalidValue);
+    /// Reads more input after the end of `src`, discarding what has been
+    /// consumed. Returns `false` at the end of input, when reading failed
+    /// or when the input pushed so far has run out.
+    fn fill(&mut self) -> bool {
+        if let Some(ref chunks) = self.push {
+            if !chunks.is_invalid() {
+                self.starved = true;
+            } else if self.input_error.is_none() {
+                let end = self.src.len();
+                self.input_error = Some(self.error_at(JsonErrorKind::InvalidUtf8, end, &[]));
+            }
+            return false;
+        }
+        if self.reader.is_none() || self.input_error.is_some() {
+            return false;
+        }
+
+        self.discard_consumed();
+
+        let end = self.src.len();
+        let res = match self.reader {
+            Some(ref mut reader) => reader.read_into(self.src.to_mut()),
+            None => unreachable!(),
+        };
+        let err = match res {
+            Ok(n) => match self.opts.max_input_len {
+                Some(max) if self.base + self.src.len() > max => {
+                    self.error_at(JsonErrorKind::InputTooLarge, max - self.base, &[])
                 }
+                _ => return n > 0,
+            },
+            Err(ReadError::InvalidUtf8) => self.error_at(JsonErrorKind::InvalidUtf8, end, &[]),
+            Err(ReadError::Io(err)) => {
+                JsonError::io(err, self.position_at(end)).with_path(self.pointer())
             }
-            Ok(())
+        };
+        self.input_error = Some(err);
+        false
+    }
+
+    /// Drops the consumed part of `src`, unless it is pinned.
+    fn discard_consumed(&mut self) {
+        if !self.pinned && self.offset > 0 {
+            let consumed = self.offset;
+            self.position_at(consumed);
+            self.src.to_mut().drain(..consumed);
+            self.base += consumed;
+            self.offset = 0;
         }
     }
 
-    fn parse_null(&mut self) -> JsonResult<JsonValue> {
-        JsonContext::parse_literal("null")(self).map(|_| JsonValue::Null)
+    pub fn peek(&mut self) -> Option<u8> {
+        if self.offset == self.src.len() {
+            self.fill();
+        }
+        self.src.get(self.offset).copied()
     }
 
-    fn parse_true(&mut self) -> JsonResult<JsonValue> {
-        JsonContext::parse_literal("true")(self).map(|_| JsonValue::Boolean(true))
+    fn next_byte(&mut self) -> Option<u8> {
+        let b = self.peek()?;
+        self.offset += 1;
+        Some(b)
     }
 
-    fn parse_false(&mut self) -> JsonResult<JsonValue> {
-        JsonContext::parse_literal("false")(self).map(|_| JsonValue::Boolean(false))
+    /// Consumes the next byte, which must be ASCII.
+    fn consume(&mut self) -> Option<u8> {
+        self.next_byte()
     }
 
-    fn parse_number(&mut self) -> JsonResult<JsonValue> {
+    /// Computes the line and column of `offset` by counting from the
+    /// nearest earlier position computed, so that positions are only paid
+    /// for when an error or warning is reported.
+    fn position_at(&self, offset: usize) -> Position {
+        let mut pos = self.checkpoint.get();
+        let mut from = pos.offset - self.base;
+        if from > offset {
+            // only reachable before anything has been discarded
+            pos = Position::start();
+            from = 0;
+        }
+        for &b in &self.src[from..offset] {
+            if b == b'\n' {
+                pos.line += 1;
+                pos.column = 1;
+            } else if b & 0xC0 != 0x80 {
+                // not a UTF-8 continuation byte
+                pos.column += 1;
+            }
+        }
+        pos.offset = self.base + offset;
+        self.checkpoint.set(pos);
+        pos
+    }
+
+    /// Formats the current path stack as a JSON Pointer.
+    fn pointer(&self) -> String {
         let mut s = String::new();
-        s.push(self.consume().unwrap());
+        for seg in &self.path {
+            s.push('/');
+            match seg {
+                PathSegment::Index(i) => s.push_str(&i.to_string()),
+                PathSegment::Key(k) => s.push_str(&k.replace('~', "~0").replace('/', "~1")),
+            }
+        }
+        s
+    }
+
+    /// Creates an error located at the next unconsumed char.
+    pub fn error(&self, kind: JsonErrorKind) -> JsonError {
+        self.error_at(kind, self.offset, &[])
+    }
+
+    fn error_expecting(&self, kind: JsonErrorKind, expected: &'static [&'static str]) -> JsonError {
+        self.error_at(kind, self.offset, expected)
+    }
+
+    fn error_at(
+        &self,
+        kind: JsonErrorKind,
+        offset: usize,
+        expected: &'static [&'static str],
+    ) -> JsonError {
+        let err = self.diagnostic(kind, offset, expected);
+        trace_event!(
+            debug,
+            "error",
+            kind = kind.message(),
+            offset = self.base + offset,
+            line = err.line(),
+            column = err.column(),
+            path = err.path(),
+        );
+        err
+    }
+
+    fn diagnostic(
+        &self,
+        kind: JsonErrorKind,
+        offset: usize,
+        expected: &'static [&'static str],
+    ) -> JsonError {
+        JsonError::with_expected(kind, self.position_at(offset), expected).with_path(self.pointer())
+    }
+
+    fn warning(&mut self, kind: JsonErrorKind, offset: usize) {
+        let warning = self.diagnostic(kind, offset, &[]);
+        trace_event!(
+            debug,
+            "warning",
+            kind = kind.message(),
+            offset = self.base + offset,
+            line = warning.line(),
+            column = warning.column(),
+            path = warning.path(),
+        );
+        self.warnings.push(warning);
+    }
+
+    /// Traces the start of the value whose first byte is `b`, unless `b`
+    /// cannot start a value.
+    fn trace_value_start(&self, b: u8) {
+        if let Some(kind) = value_kind(b) {
+            trace_event!(
+                trace,
+                "value_start",
+                kind = kind,
+                offset = self.base + self.offset
+            );
+        }
+    }
+
+    fn trace_value_end(&self, b: u8) {
+        if let Some(kind) = value_kind(b) {
+            trace_event!(
+                trace,
+                "value_end",
+                kind = kind,
+                offset = self.base + self.offset
+            );
+        }
+    }
+
+    /// The source text with its full lifetime, unless it is read or pushed.
+    fn borrowed_src(&self) -> Option<&'a [u8]> {
+        match self.src {
+            Cow::Borrowed(src) => Some(src),
+            Cow::Owned(_) => None,
+        }
+    }
+
+    /// Stands for running out of pushed input, and is never reported.
+    fn incomplete(&self) -> JsonError {
+        JsonError::new(JsonErrorKind::UnexpectedEnd, Position::start())
+    }
+
+    /// Fails if the input pushed so far has run out, so that the current
+    /// step can be run again once more has arrived.
+    fn await_input(&mut self) -> JsonResult<()> {
+        if self.peek().is_none() && self.starved {
+            return Err(self.incomplete());
+        }
+        Ok(())
+    }
+
+    fn peek_or_end(&mut self, expected: &'static [&'static str]) -> JsonResult<u8> {
+        match self.peek() {
+            Some(ch) => Ok(ch),
+            None if self.starved => Err(self.incomplete()),
+            None => Err(self.error_expecting(JsonErrorKind::UnexpectedEnd, expected)),
+        }
+    }
+
+    pub fn parse_value<B: JsonBuilder<'a>>(&mut self, builder: &mut B) -> JsonResult<()> {
+        self.parse_whitespace()?;
+        let b = self.peek_or_end(EXPECT_VALUE)?;
+        self.trace_value_start(b);
+        match b {
+            b'[' => self.parse_nested(builder, JsonContext::parse_array),
+            b'{' => self.parse_nested(builder, JsonContext::parse_object),
+            b => self.parse_scalar(b, builder),
+        }?;
+        self.trace_value_end(b);
+        self.parse_value_end()
+    }
+
+    /// Parses a value like `parse_value`, but keeps the open arrays and
+    /// objects on a heap-allocated stack instead of recursing into them, so
+    /// that nesting is bounded only by `max_depth` and memory.
+    pub fn parse_value_iterative<B: JsonBuilder<'a>>(&mut self, builder: &mut B) -> JsonResult<()> {
+        let mut stack = <Vec<Open>>::new();
+
+        'value: loop {
+            self.parse_whitespace()?;
+            let b = self.peek_or_end(EXPECT_VALUE)?;
+            self.trace_value_start(b);
+            match b {
+                b'[' => {
+                    self.enter_nested()?;
+                    self.consume();
+                    builder.begin_array();
+                    self.parse_whitespace()?;
+                    if self.peek_or_end(EXPECT_VALUE_OR_BRACKET)? != b']' {
+                        stack.push(Open::Array(0));
+                        self.push_index(0)?;
+                        continue 'value;
+                    }
+                    self.consume();
+                    self.leave_nested();
+                    builder.end_array();
+                }
+                b'{' => {
+                    self.enter_nested()?;
+                    self.consume();
+                    builder.begin_object();
+                    self.parse_whitespace()?;
+                    if self.peek_or_end(EXPECT_KEY_OR_BRACE)? != b'}' {
+                        let mut members = Members::new(self.opts.duplicate_keys);
+                        self.parse_member_key(&mut members, builder)?;
+                        stack.push(Open::Object(members));
+                        continue 'value;
+                    }
+                    self.consume();
+                    self.leave_nested();
+                    builder.end_object();
+                }
+                b => self.parse_scalar(b, builder)?,
+            }
+            self.trace_value_end(b);
+
+            // close every container that ends right after the value
+            loop {
+                self.parse_value_end()?;
+                match stack.last_mut() {
+                    None => return Ok(()),
+                    Some(Open::Array(len)) => {
+                        self.path.pop();
+                        *len += 1;
+                        match self.peek_or_end(EXPECT_COMMA_OR_BRACKET)? {
+                            b',' => {
+                                if self.parse_array_comma()? {
+                                    self.push_index(*len)?;
+                                    continue 'value;
+                                }
+                                // the closing bracket after a trailing comma
+                                self.consume()
+                            }
+                            b']' => self.consume(),
+                            _ => {
+                                return Err(self.error_expecting(
+                                    JsonErrorKind::ExpectedCommaOrBracket,
+                                    EXPECT_COMMA_OR_BRACKET,
+                                ))
+                            }
+                        };
+                    }
+                    Some(Open::Object(members)) => {
+                        self.pop_member_key();
+                        members.count += 1;
+                        match self.peek_or_end(EXPECT_COMMA_OR_BRACE)? {
+                            b',' => {
+                                self.consume();
+                                if self.parse_member_key(members, builder)? {
+                                    continue 'value;
+                                }
+                                // the closing brace after a trailing comma
+                                self.consume()
+                            }
+                            b'}' => self.consume(),
+                            _ => {
+                                return Err(self.error_expecting(
+                                    JsonErrorKind::ExpectedCommaOrBrace,
+                                    EXPECT_COMMA_OR_BRACE,
+                                ))
+                            }
+                        };
+                    }
+                }
+
+                match stack.pop() {
+                    Some(Open::Array(_)) => {
+                        self.trace_value_end(b'[');
+                        builder.end_array();
+                    }
+                    Some(Open::Object(_)) => {
+                        self.trace_value_end(b'{');
+                        builder.end_object();
+                    }
+                    None => unreachable!(),
+                }
+                self.leave_nested();
+            }
+        }
+    }
+
+    fn parse_scalar<B: JsonBuilder<'a>>(&mut self, b: u8, builder: &mut B) -> JsonResult<()> {
+        match b {
+            b'n' => self.parse_literal(EXPECT_NULL, 0, builder),
+            b't' => self.parse_literal(EXPECT_TRUE, 0, builder),
+            b'f' => self.parse_literal(EXPECT_FALSE, 0, builder),
+            b'"' => self.parse_string(builder),
+            b'-' | b'0'..=b'9' => self.parse_number(0, builder),
+            _ if self.opts.dialect != Dialect::Json5 => {
+                Err(self.error_expecting(JsonErrorKind::InvalidValue, EXPECT_VALUE))
+            }
+            b'\'' => self.parse_string(builder),
+            b'+' | b'.' => self.parse_number(0, builder),
+            b'I' => self.parse_literal(EXPECT_INFINITY, 0, builder),
+            b'N' => self.parse_literal(EXPECT_NAN, 0, builder),
+            _ => Err(self.error_expecting(JsonErrorKind::InvalidValue, EXPECT_VALUE)),
+        }
+    }
 
-        while let Some(ch) = self.peek() {
-            if ch.is_digit(10) || ".eE-+".contains(ch) {
-                s.push(ch);
+    fn parse_value_end(&mut self) -> JsonResult<()> {
+        self.check_value_end()?;
+        self.parse_whitespace()
+    }
+
+    /// Checks that the char right after a value may follow it.
+    fn check_value_end(&mut self) -> JsonResult<()> {
+        match self.peek() {
+            Some(b',') | Some(b']') | Some(b'}') | None => Ok(()),
+            Some(b) if is_whitespace(b) || self.space_len() > 0 => Ok(()),
+            Some(b'/') if self.opts.dialect.comments() => Ok(()),
+            Some(_) => {
+                Err(self.error_expecting(JsonErrorKind::TrailingCharacters, EXPECT_VALUE_END))
+            }
+        }
+    }
+
+    fn check_limit(&self, len: usize, limit: Option<usize>, kind: JsonErrorKind) -> JsonResult<()> {
+        match limit {
+            Some(max) if len > max => Err(self.error(kind)),
+            _ => Ok(()),
+        }
+    }
+
+    fn enter_nested(&mut self) -> JsonResult<()> {
+        if let Some(max_depth) = self.opts.max_depth {
+            if self.depth >= max_depth {
+                return Err(self.error(JsonErrorKind::DepthLimitExceeded));
+            }
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn leave_nested(&mut self) {
+        self.depth -= 1;
+    }
+
+    fn parse_nested<B: JsonBuilder<'a>>(
+        &mut self,
+        builder: &mut B,
+        parse: fn(&mut Self, &mut B) -> JsonResult<()>,
+    ) -> JsonResult<()> {
+        self.enter_nested()?;
+        parse(self, builder)?;
+        self.leave_nested();
+        Ok(())
+    }
+
+    /// Skips whitespace, and comments if the dialect allows them.
+    #[inline]
+    fn parse_whitespace(&mut self) -> JsonResult<()> {
+        // most tokens follow the one before them right away; JSON5
+        // whitespace is either below `!` or not ASCII
+        match self.src.get(self.offset) {
+            Some(&b) if b > b' ' && b < 0x80 && b != b'/' && self.comment.is_none() => Ok(()),
+            _ => self.skip_whitespace(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> JsonResult<()> {
+        if let Some(comment) = self.comment.take() {
+            self.parse_comment(comment)?;
+        }
+        loop {
+            // skip a run of whitespace in one go, as in pretty-printed text
+            let run = self.src[self.offset..]
+                .iter()
+                .take_while(|&&b| is_whitespace(b))
+                .count();
+            self.offset += run;
+            let b = match self.peek() {
+                Some(b) => b,
+                None => break,
+            };
+            if is_whitespace(b) {
+                // more input has been read
+                continue;
+            } else if b == b'/' && self.opts.dialect.comments() {
                 self.consume();
+                self.parse_comment(Comment::Slash)?;
             } else {
-                break;
+                match self.space_len() {
+                    0 => break,
+                    len => self.offset += len,
+                }
             }
         }
+        Ok(())
+    }
 
-        if validate_number(&s) {
-            let num: f64 = s.parse().expect("illegal float number");
-            if !num.is_infinite() {
-                Ok(JsonValue::Number(num))
-            } else {
-                Err(JsonError::NumberTooBig)
+    /// The length in bytes of the JSON5 whitespace char at the current
+    /// offset, or 0 if there is none.
+    fn space_len(&self) -> usize {
+        if self.opts.dialect != Dialect::Json5 {
+            return 0;
+        }
+        let len = match self.src.get(self.offset) {
+            None => return 0,
+            Some(&b) if b < 0x80 => 1,
+            Some(&b) if b < 0xE0 => 2,
+            Some(&b) if b < 0xF0 => 3,
+            Some(_) => 4,
+        };
+        // `src` always ends at a char boundary
+        let ch =
+            unsafe { std::str::from_utf8_unchecked(&self.src[self.offset..self.offset + len]) };
+        match ch.chars().next() {
+            Some(ch) if is_json5_whitespace(ch) => len,
+            _ => 0,
+        }
+    }
+
+    /// Parses the rest of a comment, keeping its progress in `comment` if
+    /// the input pushed so far runs out.
+    fn parse_comment(&mut self, mut comment: Comment) -> JsonResult<()> {
+        loop {
+            let b = match self.peek() {
+                Some(b) => b,
+                None if self.starved => {
+                    self.comment = Some(comment);
+                    return Err(self.incomplete());
+                }
+                None => {
+                    return match comment {
+                        Comment::Line => Ok(()),
+                        Comment::Slash => {
+                            Err(self.error_expecting(JsonErrorKind::UnexpectedEnd, EXPECT_COMMENT))
+                        }
+                        Comment::Block { .. } => {
+                            Err(self
+                                .error_expecting(JsonErrorKind::UnexpectedEnd, EXPECT_COMMENT_END))
+                        }
+                    }
+                }
+            };
+            comment = match (comment, b) {
+                (Comment::Slash, b'/') => Comment::Line,
+                (Comment::Slash, b'*') => Comment::Block { star: false },
+                (Comment::Slash, _) => {
+                    return Err(self.error_expecting(JsonErrorKind::InvalidComment, EXPECT_COMMENT))
+                }
+                // leave the line break to the caller
+                (Comment::Line, b'\n') | (Comment::Line, b'\r') => return Ok(()),
+                (Comment::Line, _) => Comment::Line,
+                (Comment::Block { star: true }, b'/') => {
+                    self.consume();
+                    return Ok(());
+                }
+                (Comment::Block { .. }, b) => Comment::Block { star: b == b'*' },
+            };
+            // may stop inside a char, but never returns there
+            self.offset += 1;
+        }
+    }
+
+    /// Parses the literal `expected[0]`, of which `matched` bytes have
+    /// already been consumed.
+    fn parse_literal<B: JsonBuilder<'a>>(
+        &mut self,
+        expected: &'static [&'static str],
+        matched: usize,
+        builder: &mut B,
+    ) -> JsonResult<()> {
+        let lit = expected[0];
+        for (i, b) in lit.bytes().enumerate().skip(matched) {
+            match self.peek() {
+                Some(ch) if ch == b => {
+                    self.consume();
+                }
+                Some(_) => {
+                    return Err(self.error_expecting(JsonErrorKind::InvalidLiteral, expected))
+                }
+                None if self.starved => {
+                    self.partial = Some(Partial::Literal {
+                        expected,
+                        matched: i,
+                    });
+                    return Err(self.incomplete());
+                }
+                None => return Err(self.error_expecting(JsonErrorKind::UnexpectedEnd, expected)),
             }
+        }
+        match lit {
+            "null" => builder.null(),
+            "true" => builder.boolean(true),
+            "false" => builder.boolean(false),
+            "NaN" | "+NaN" | "-NaN" => builder.number(f64::NAN.into()),
+            "-Infinity" => builder.number(f64::NEG_INFINITY.into()),
+            _ => builder.number(f64::INFINITY.into()),
+        }
+        Ok(())
+    }
+
+    /// Scans the number literal at the current offset.
+    fn scan_number_here(&self, json5: bool) -> NumberScan {
+        let rest = &self.src[self.offset..];
+        if json5 {
+            scan_json5_number(rest)
         } else {
-            Err(JsonError::InvalidValue)
+            scan_number(rest)
         }
     }
 
-    fn parse_hex4(&mut self) -> JsonResult<char> {
-        let mut ans: u16 = 0;
+    /// Reads input until the number chars at the current offset are
+    /// followed by something else, the first `scanned` of them being known
+    /// already.
+    fn buffer_number(&mut self, scanned: usize, json5: bool) -> JsonResult<()> {
+        let mut len = scanned;
+        loop {
+            len += self.src[self.offset + len..]
+                .iter()
+                .take_while(|&&b| match b {
+                    b'0'..=b'9' | b'.' | b'e' | b'E' | b'-' | b'+' => true,
+                    // hex digits, `Infinity` and `NaN`
+                    _ => json5 && b.is_ascii_alphabetic(),
+                })
+                .count();
+            // enough is buffered to fail the length check
+            let too_long = self.opts.max_number_len.is_some_and(|max| len > max);
+            if too_long || self.offset + len < self.src.len() {
+                return Ok(());
+            }
+            if !self.fill() {
+                if self.starved {
+                    self.partial = Some(Partial::Number { scanned: len });
+                    return Err(self.incomplete());
+                }
+                return Ok(());
+            }
+        }
+    }
 
-        for _ in 0..4 {
-            let ch = self.consume().ok_or(JsonError::UnexpectedEnd)?;
-            let t = ch.to_digit(16).ok_or(JsonError::InvalidValue)? as u16;
-            ans = (ans << 4) | t;
+    /// Parses a number, of which the first `scanned` bytes are known to be
+    /// number chars.
+    fn parse_number<B: JsonBuilder<'a>>(
+        &mut self,
+        scanned: usize,
+        builder: &mut B,
+    ) -> JsonResult<()> {
+        let json5 = self.opts.dialect == Dialect::Json5;
+        // scan in place, unless the literal may go on past the end of
+        // `src`; a resumed literal is buffered before it is scanned, so
+        // that it is not scanned again for every chunk pushed
+        let mut scan = match scanned {
+            0 => self.scan_number_here(json5),
+            _ => NumberScan::default(),
+        };
+        if scanned > 0 || self.offset + scan.len == self.src.len() {
+            self.buffer_number(scanned, json5)?;
+            scan = self.scan_number_here(json5);
+        }
+        let start = self.offset;
+        if json5 {
+            let expected = match self.src[start..] {
+                [b'+', b'I', ..] => Some(EXPECT_POS_INFINITY),
+                [b'-', b'I', ..] => Some(EXPECT_NEG_INFINITY),
+                [b'+', b'N', ..] => Some(EXPECT_POS_NAN),
+                [b'-', b'N', ..] => Some(EXPECT_NEG_NAN),
+                _ => None,
+            };
+            if let Some(expected) = expected {
+                return self.parse_literal(expected, 0, builder);
+            }
+        }
+        if let Some(max) = self.opts.max_number_len {
+            if scan.len > max {
+                return Err(self.error_at(JsonErrorKind::NumberTooLong, start, &[]));
+            }
+        }
+        if !scan.valid {
+            return Err(self.error_at(JsonErrorKind::InvalidNumber, start, &[]));
+        }
+        self.offset += scan.len;
+        // only ASCII bytes have been scanned
+        let s = unsafe { std::str::from_utf8_unchecked(&self.src[start..self.offset]) };
+
+        if self.opts.arbitrary_precision && (!json5 || validate_number(s)) {
+            builder.number(Number::from_literal_unchecked(s));
+            return Ok(());
+        }
+        if scan.is_integer && !scan.truncated {
+            let num = match (scan.negative, scan.mantissa) {
+                (false, m) => Some(Number::from(m)),
+                // keep the sign of `-0`
+                (true, 0) => Some(Number::from(-0.0)),
+                (true, m) if m <= i64::MIN.unsigned_abs() => {
+                    Some(Number::from((m as i64).wrapping_neg()))
+                }
+                (true, _) => None,
+            };
+            if let Some(num) = num {
+                builder.number(num);
+                return Ok(());
+            }
+        }
+        if scan.hex {
+            return Err(self.error_at(JsonErrorKind::NumberTooBig, start, &[]));
         }
 
-        Ok(unsafe { std::char::from_u32_unchecked(u32::from(ans)) })
+        let num = match scan.fast_f64() {
+            Some(num) => num,
+            None => {
+                let num: f64 = s.parse().expect("illegal float number");
+                if num.is_infinite() {
+                    return Err(self.error_at(JsonErrorKind::NumberTooBig, start, &[]));
+                }
+                num
+            }
+        };
+        // the fast path rounds exactly, but the literal may still not fit
+        if self.opts.lossy_numbers != LossyNumberPolicy::Allow {
+            if let Some(kind) = conversion_loss(s, num) {
+                if self.opts.lossy_numbers == LossyNumberPolicy::Reject {
+                    return Err(self.error_at(kind, start, &[]));
+                }
+                self.warning(kind, start);
+            }
+        }
+        builder.number(num.into());
+        Ok(())
     }
 
-    fn parse_escape_char(&mut self) -> JsonResult<char> {
-        let ch = self.consume().ok_or(JsonError::UnexpectedEnd)?;
-        match ch {
-            '"' => Ok('"'),
-            '\\' => Ok('\\'),
-            '/' => Ok('/'),
-            'b' => Ok('\u{8}'),
-            'f' => Ok('\u{c}'),
-            'n' => Ok('\n'),
-            'r' => Ok('\r'),
-            't' => Ok('\t'),
-            'u' => self.parse_hex4(),
-            _ => Err(JsonError::InvalidValue),
+    fn push_lone_surrogate(&self, unit: u16, start: usize, buf: &mut StrBuf<'a>) -> JsonResult<()> {
+        match self.opts.lone_surrogates {
+            SurrogatePolicy::Reject => Err(self.error_at(JsonErrorKind::LoneSurrogate, start, &[])),
+            SurrogatePolicy::Replace => {
+                buf.push('\u{FFFD}');
+                Ok(())
+            }
+            SurrogatePolicy::Preserve => {
+                buf.push_surrogate(unit);
+                Ok(())
+            }
         }
     }
 
-    fn parse_string_raw(&mut self) -> JsonResult<String> {
-        if '"' != self.consume().ok_or(JsonError::UnexpectedEnd)? {
-            return Err(JsonError::InvalidValue);
+    /// Parses the rest of the escape sequence in `state.escape`, combining
+    /// a `\u` escape of a high surrogate with a following one of a low
+    /// surrogate into a single char.
+    fn parse_escape(&mut self, state: &mut StrState<'a>) -> JsonResult<()> {
+        loop {
+            match state.escape {
+                Escape::None => return Ok(()),
+                Escape::Backslash { start, high } => {
+                    let b = self.peek_or_end(EXPECT_ESCAPE)?;
+                    if b == b'u' {
+                        self.consume();
+                        state.escape = Escape::Hex {
+                            start,
+                            unit: 0,
+                            digits: 0,
+                            high,
+                        };
+                        continue;
+                    }
+                    if state.quote == 0 {
+                        // the only escapes allowed in unquoted keys
+                        return Err(self.error_expecting(
+                            JsonErrorKind::InvalidEscape,
+                            EXPECT_IDENTIFIER_ESCAPE,
+                        ));
+                    }
+                    if let Some((unit, at)) = high {
+                        self.push_lone_surrogate(unit, at, &mut state.buf)?;
+                    }
+                    let ch = match b {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        _ if self.opts.dialect != Dialect::Json5 => {
+                            return Err(
+                                self.error_expecting(JsonErrorKind::InvalidEscape, EXPECT_ESCAPE)
+                            )
+                        }
+                        b'v' => '\u{b}',
+                        b'0' => {
+                            self.consume();
+                            state.escape = Escape::Zero;
+                            continue;
+                        }
+                        b'1'..=b'9' => return Err(self.error(JsonErrorKind::InvalidEscape)),
+                        b'x' => {
+                            self.consume();
+                            state.escape = Escape::Hex {
+                                start,
+                                unit: 0,
+                                digits: 2,
+                                high: None,
+                            };
+                            continue;
+                        }
+                        // line continuations
+                        b'\n' => {
+                            self.consume();
+                            state.escape = Escape::None;
+                            continue;
+                        }
+                        b'\r' => {
+                            self.consume();
+                            state.escape = Escape::CarriageReturn;
+                            continue;
+                        }
+                        0x80..=0xFF => {
+                            let rest = &self.src[self.offset..];
+                            if rest.starts_with("\u{2028}".as_bytes())
+                                || rest.starts_with("\u{2029}".as_bytes())
+                            {
+                                self.offset += 3;
+                            }
+                            // any other char stands for itself and is left
+                            // to the caller
+                            state.escape = Escape::None;
+                            continue;
+                        }
+                        b => char::from(b),
+                    };
+                    self.consume();
+                    state.buf.push(ch);
+                    state.escape = Escape::None;
+                }
+                Escape::Hex {
+                    start,
+                    mut unit,
+                    mut digits,
+                    high,
+                } => {
+                    while digits < 4 {
+                        let t = match char::from(self.peek_or_end(EXPECT_HEX_DIGIT)?).to_digit(16) {
+                            Some(t) => t as u16,
+                            None => {
+                                return Err(self.error_expecting(
+                                    JsonErrorKind::InvalidUnicodeEscape,
+                                    EXPECT_HEX_DIGIT,
+                                ))
+                            }
+                        };
+                        self.consume();
+                        unit = (unit << 4) | t;
+                        digits += 1;
+                        state.escape = Escape::Hex {
+                            start,
+                            unit,
+                            digits,
+                            high,
+                        };
+                    }
+
+                    if let Some((high, at)) = high {
+                        if let 0xDC00..=0xDFFF = unit {
+                            let n = 0x10000
+                                + ((u32::from(high) - 0xD800) << 10)
+                                + (u32::from(unit) - 0xDC00);
+                            state.buf.push(unsafe { std::char::from_u32_unchecked(n) });
+                            state.escape = Escape::None;
+                            continue;
+                        }
+                        self.push_lone_surrogate(high, at, &mut state.buf)?;
+                    }
+                    match unit {
+                        0xD800..=0xDBFF => state.escape = Escape::High { start, unit },
+                        0xDC00..=0xDFFF => {
+                            self.push_lone_surrogate(unit, start, &mut state.buf)?;
+                            state.escape = Escape::None;
+                        }
+                        _ => {
+                            state
+                                .buf
+                                .push(unsafe { std::char::from_u32_unchecked(u32::from(unit)) });
+                            state.escape = Escape::None;
+                        }
+                    }
+                }
+                Escape::High { start, unit } => match self.peek() {
+                    Some(b'\\') => {
+                        state.escape = Escape::Backslash {
+                            start: self.offset,
+                            high: Some((unit, start)),
+                        };
+                        self.consume();
+                    }
+                    None if self.starved => return Err(self.incomplete()),
+                    _ => {
+                        self.push_lone_surrogate(unit, start, &mut state.buf)?;
+                        state.escape = Escape::None;
+                    }
+                },
+                Escape::CarriageReturn => match self.peek() {
+                    Some(b'\n') => {
+                        self.consume();
+                        state.escape = Escape::None;
+                    }
+                    None if self.starved => return Err(self.incomplete()),
+                    _ => state.escape = Escape::None,
+                },
+                Escape::Zero => match self.peek() {
+                    // a legacy octal escape
+                    Some(b'0'..=b'9') => return Err(self.error(JsonErrorKind::InvalidEscape)),
+                    None if self.starved => return Err(self.incomplete()),
+                    _ => {
+                        state.buf.push('\0');
+                        state.escape = Escape::None;
+                    }
+                },
+            }
         }
+    }
 
-        let mut s = String::new();
+    /// Parses a string, pinning `src` until the caller has reported any
+    /// errors located at its start and clears `pinned`.
+    fn parse_string_raw(&mut self) -> JsonResult<StrBuf<'a>> {
+        let state = StrState {
+            start: self.offset,
+            quote: self.src[self.offset],
+            buf: StrBuf::new(),
+            escape: Escape::None,
+        };
+        self.pinned = true;
+        self.consume();
+        self.parse_string_rest(state)
+    }
+
+    /// Parses the rest of a string, keeping it in `partial` if the input
+    /// pushed so far runs out.
+    fn parse_string_rest(&mut self, mut state: StrState<'a>) -> JsonResult<StrBuf<'a>> {
+        match self.parse_string_chars(&mut state) {
+            Ok(()) => Ok(state.buf),
+            Err(err) => {
+                if self.starved {
+                    self.partial = Some(Partial::String(state));
+                }
+                Err(err)
+            }
+        }
+    }
 
+    fn parse_string_chars(&mut self, state: &mut StrState<'a>) -> JsonResult<()> {
         loop {
-            match self.consume().ok_or(JsonError::UnexpectedEnd)? {
-                '"' => return Ok(s),
-                '\\' => s.push(self.parse_escape_char()?),
-                c if is_unescaped_char(c) => s.push(c),
-                _ => return Err(JsonError::InvalidValue),
+            if !matches!(state.escape, Escape::None) {
+                self.parse_escape(state)?;
+            }
+            let run = self.string_run(state.quote);
+            match self.borrowed_src() {
+                Some(src) if state.buf.bytes.is_empty() => {
+                    state.buf.bytes = Cow::Borrowed(&src[self.offset..self.offset + run]);
+                }
+                _ => state
+                    .buf
+                    .push_run(&self.src[self.offset..self.offset + run]),
+            }
+            self.offset += run;
+            if let Some(max) = self.opts.max_string_len {
+                if state.buf.bytes.len() > max {
+                    return Err(self.error_at(JsonErrorKind::StringTooLong, state.start, &[]));
+                }
+            }
+            let expected = match state.quote {
+                b'"' => EXPECT_STRING_END,
+                _ => EXPECT_SINGLE_QUOTE_END,
+            };
+            match self.peek_or_end(expected)? {
+                b if b == state.quote => {
+                    self.consume();
+                    return Ok(());
+                }
+                b'\\' => {
+                    state.escape = Escape::Backslash {
+                        start: self.offset,
+                        high: None,
+                    };
+                    self.consume();
+                }
+                // more input has been read
+                _ if self.string_run(state.quote) > 0 => {}
+                _ => return Err(self.error(JsonErrorKind::ControlCharacterInString)),
+            }
+        }
+    }
+
+    /// The length of the run of bytes at the current offset that stand for
+    /// themselves in a string quoted with `quote`.
+    fn string_run(&self, quote: u8) -> usize {
+        let rest = self.src[self.offset..].iter();
+        if self.opts.dialect == Dialect::Json5 {
+            // control chars other than line breaks are allowed
+            rest.take_while(|&&b| b != quote && !matches!(b, b'\\' | b'\n' | b'\r'))
+                .count()
+        } else {
+            unescaped_len(&self.src[self.offset..])
+        }
+    }
+
+    /// Passes the contents of a string to `builder`, unpinning `src`.
+    fn string_value<B: JsonBuilder<'a>>(&mut self, buf: StrBuf<'a>, builder: &mut B) {
+        self.pinned = false;
+        match buf.into_text() {
+            Ok(s) => builder.string(s),
+            Err(bytes) => builder.wtf8_string(bytes),
+        }
+    }
+
+    fn parse_string<B: JsonBuilder<'a>>(&mut self, builder: &mut B) -> JsonResult<()> {
+        let buf = self.parse_string_raw()?;
+        self.string_value(buf, builder);
+        Ok(())
+    }
+
+    /// Finishes parsing a scalar cut off by the end of the input pushed
+    /// so far.
+    fn resume_scalar<B: JsonBuilder<'a>>(
+        &mut self,
+        partial: Partial<'a>,
+        builder: &mut B,
+    ) -> JsonResult<()> {
+        match partial {
+            Partial::Literal { expected, matched } => {
+                self.parse_literal(expected, matched, builder)
+            }
+            Partial::Number { scanned } => self.parse_number(scanned, builder),
+            Partial::String(state) => {
+                let buf = self.parse_string_rest(state)?;
+                self.string_value(buf, builder);
+                Ok(())
             }
+            Partial::Identifier(_) => unreachable!(),
         }
     }
 
-    fn parse_string(&mut self) -> JsonResult<JsonValue> {
-        self.parse_string_raw().map(JsonValue::String)
+    fn push_index(&mut self, index: usize) -> JsonResult<()> {
+        self.check_limit(
+            index + 1,
+            self.opts.max_array_len,
+            JsonErrorKind::TooManyElements,
+        )?;
+        self.path.push(PathSegment::Index(index));
+        Ok(())
     }
 
-    fn parse_array(&mut self) -> JsonResult<JsonValue> {
+    fn parse_element<B: JsonBuilder<'a>>(
+        &mut self,
+        index: usize,
+        builder: &mut B,
+    ) -> JsonResult<()> {
+        self.push_index(index)?;
+        self.parse_value(builder)?;
+        self.path.pop();
+        Ok(())
+    }
+
+    /// Consumes a comma after an element, returning `false` if it is a
+    /// trailing comma followed by the closing bracket.
+    fn parse_array_comma(&mut self) -> JsonResult<bool> {
         self.consume();
-        self.parse_whitespace();
+        self.parse_whitespace()?;
+        if let Some(b']') = self.peek() {
+            if self.opts.dialect.trailing_commas() {
+                return Ok(false);
+            }
+            return Err(self.error(JsonErrorKind::TrailingComma));
+        }
+        Ok(true)
+    }
 
-        let mut arr = <Vec<JsonValue>>::new();
-        match self.peek().ok_or(JsonError::UnexpectedEnd)? {
-            ']' => {
+    fn parse_array<B: JsonBuilder<'a>>(&mut self, builder: &mut B) -> JsonResult<()> {
+        self.consume();
+        builder.begin_array();
+        self.parse_whitespace()?;
+
+        match self.peek_or_end(EXPECT_VALUE_OR_BRACKET)? {
+            b']' => {
                 self.consume();
-                return Ok(JsonValue::Array(arr));
+                builder.end_array();
+                return Ok(());
+            }
+            _ => self.parse_element(0, builder)?,
+        };
+
+        let mut index = 0;
+        loop {
+            match self.peek_or_end(EXPECT_COMMA_OR_BRACKET)? {
+                b',' => {
+                    if self.parse_array_comma()? {
+                        index += 1;
+                        self.parse_element(index, builder)?;
+                    }
+                }
+                b']' => {
+                    self.consume();
+                    builder.end_array();
+                    return Ok(());
+                }
+                _ => {
+                    return Err(self.error_expecting(
+                        JsonErrorKind::ExpectedCommaOrBracket,
+                        EXPECT_COMMA_OR_BRACKET,
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Parses the key of the next member and the colon after it, pushing
+    /// the key onto the path until `pop_member_key` is called. Returns
+    /// `false` if a trailing comma is followed by the closing brace instead.
+    fn parse_member_key<B: JsonBuilder<'a>>(
+        &mut self,
+        members: &mut Members,
+        builder: &mut B,
+    ) -> JsonResult<bool> {
+        if !self.parse_key_start(members)? {
+            return Ok(false);
+        }
+        let start = self.offset;
+        let buf = self.parse_key_raw()?;
+        self.push_member_key(start, buf, members)?;
+        self.parse_colon()?;
+        builder.key(self.member_key());
+        Ok(true)
+    }
+
+    /// Parses up to the start of the next key, returning `false` if a
+    /// trailing comma is followed by the closing brace instead.
+    fn parse_key_start(&mut self, members: &Members) -> JsonResult<bool> {
+        self.parse_whitespace()?;
+        let b = self.peek_or_end(EXPECT_KEY)?;
+        if b == b'}' {
+            if self.opts.dialect.trailing_commas() {
+                return Ok(false);
             }
+            return Err(self.error(JsonErrorKind::TrailingComma));
+        }
+        self.check_limit(
+            members.count + 1,
+            self.opts.max_object_len,
+            JsonErrorKind::TooManyMembers,
+        )?;
+        match b {
+            b'"' => Ok(true),
+            b'\'' | b'\\' | b'a'..=b'z' | b'A'..=b'Z' | b'$' | b'_' | 0x80..=0xFF
+                if self.opts.dialect == Dialect::Json5 =>
+            {
+                Ok(true)
+            }
+            _ => Err(self.error_expecting(JsonErrorKind::ExpectedObjectKey, EXPECT_KEY)),
+        }
+    }
+
+    /// Parses a key, quoted or not, pinning `src` like `parse_string_raw`.
+    fn parse_key_raw(&mut self) -> JsonResult<StrBuf<'a>> {
+        match self.src[self.offset] {
+            b'"' | b'\'' => self.parse_string_raw(),
             _ => {
-                arr.push(self.parse_value()?);
+                let state = StrState {
+                    start: self.offset,
+                    quote: 0,
+                    buf: StrBuf::new(),
+                    escape: Escape::None,
+                };
+                self.pinned = true;
+                self.parse_identifier(state)
             }
-        };
+        }
+    }
 
+    /// Parses the rest of an unquoted JSON5 key, keeping it in `partial` if
+    /// the input pushed so far runs out.
+    fn parse_identifier(&mut self, mut state: StrState<'a>) -> JsonResult<StrBuf<'a>> {
+        match self.parse_identifier_chars(&mut state) {
+            Ok(()) => Ok(state.buf),
+            Err(err) => {
+                if self.starved {
+                    self.partial = Some(Partial::Identifier(state));
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Parses the chars of an unquoted key, which follow the ES5
+    /// IdentifierName grammar and may be written as `\u` escapes.
+    fn parse_identifier_chars(&mut self, state: &mut StrState<'a>) -> JsonResult<()> {
         loop {
-            match self.consume().ok_or(JsonError::UnexpectedEnd)? {
-                ',' => arr.push(self.parse_value()?),
-                ']' => return Ok(JsonValue::Array(arr)),
-                _ => return Err(JsonError::InvalidValue),
+            if let Escape::Backslash { start, .. }
+            | Escape::Hex { start, .. }
+            | Escape::High { start, .. } = state.escape
+            {
+                self.parse_escape(state)?;
+                // the escaped char must be one allowed where it occurs
+                let valid = match state.buf.last_char() {
+                    Some(ch) if start == state.start => is_identifier_start(ch),
+                    Some(ch) => is_identifier_part(ch),
+                    None => false,
+                };
+                if !valid {
+                    return Err(self.error_at(JsonErrorKind::InvalidUnicodeEscape, start, &[]));
+                }
+            }
+            let run = self.identifier_run(self.offset == state.start);
+            match self.borrowed_src() {
+                Some(src) if state.buf.bytes.is_empty() => {
+                    state.buf.bytes = Cow::Borrowed(&src[self.offset..self.offset + run]);
+                }
+                _ => state
+                    .buf
+                    .push_run(&self.src[self.offset..self.offset + run]),
             }
+            self.offset += run;
+            if let Some(max) = self.opts.max_string_len {
+                if state.buf.bytes.len() > max {
+                    return Err(self.error_at(JsonErrorKind::StringTooLong, state.start, &[]));
+                }
+            }
+            match self.peek() {
+                Some(b'\\') => {
+                    state.escape = Escape::Backslash {
+                        start: self.offset,
+                        high: None,
+                    };
+                    self.consume();
+                }
+                None if self.starved => return Err(self.incomplete()),
+                // more input has been read
+                Some(_) if self.identifier_run(self.offset == state.start) > 0 => {}
+                _ => break,
+            }
+        }
+        if state.buf.bytes.is_empty() {
+            return Err(self.error_expecting(JsonErrorKind::ExpectedObjectKey, EXPECT_KEY));
         }
+        Ok(())
     }
 
-    fn parse_kv(&mut self) -> JsonResult<(String, JsonValue)> {
-        self.parse_whitespace();
-        let k = self.parse_string_raw()?;
-        self.parse_whitespace();
-        match self.consume().ok_or(JsonError::UnexpectedEnd)? {
-            ':' => {
-                let v = self.parse_value()?;
-                Ok((k, v))
+    /// The length of the run of chars at the current offset that may occur
+    /// in an unquoted key, where `first` is whether it starts the key.
+    fn identifier_run(&self, first: bool) -> usize {
+        // `src` always ends at a char boundary
+        let rest = unsafe { std::str::from_utf8_unchecked(&self.src[self.offset..]) };
+        let mut chars = rest.char_indices();
+        if first && !chars.next().is_some_and(|(_, ch)| is_identifier_start(ch)) {
+            return 0;
+        }
+        chars
+            .find(|&(_, ch)| !is_identifier_part(ch))
+            .map_or(rest.len(), |(i, _)| i)
+    }
+
+    /// Pushes the key parsed from the string at `start` onto the path.
+    fn push_member_key(
+        &mut self,
+        start: usize,
+        buf: StrBuf<'a>,
+        members: &mut Members,
+    ) -> JsonResult<()> {
+        let k = match buf.into_text() {
+            Ok(k) => k,
+            Err(_) => return Err(self.error_at(JsonErrorKind::LoneSurrogate, start, &[])),
+        };
+        let duplicate = match members.keys {
+            Some(ref mut keys) => !keys.insert(k.clone().into_owned()),
+            None => false,
+        };
+        self.path.push(PathSegment::Key(k));
+        if duplicate {
+            return Err(self.error_at(JsonErrorKind::DuplicateKey, start, &[]));
+        }
+        self.pinned = false;
+        Ok(())
+    }
+
+    fn parse_colon(&mut self) -> JsonResult<()> {
+        self.parse_whitespace()?;
+        match self.peek_or_end(EXPECT_COLON)? {
+            b':' => {
+                self.consume();
+                Ok(())
             }
-            _ => Err(JsonError::MissingColon),
+            _ => Err(self.error_expecting(JsonErrorKind::MissingColon, EXPECT_COLON)),
         }
     }
 
-    fn parse_object(&mut self) -> JsonResult<JsonValue> {
+    /// The key on top of the path.
+    fn member_key(&self) -> Cow<'a, str> {
+        match self.path.last() {
+            Some(PathSegment::Key(k)) => k.clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn pop_member_key(&mut self) {
+        match self.path.pop() {
+            Some(PathSegment::Key(_)) => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn parse_member<B: JsonBuilder<'a>>(
+        &mut self,
+        members: &mut Members,
+        builder: &mut B,
+    ) -> JsonResult<()> {
+        if !self.parse_member_key(members, builder)? {
+            return Ok(());
+        }
+        self.parse_value(builder)?;
+        self.pop_member_key();
+        members.count += 1;
+        Ok(())
+    }
+
+    fn parse_object<B: JsonBuilder<'a>>(&mut self, builder: &mut B) -> JsonResult<()> {
         self.consume();
-        self.parse_whitespace();
+        builder.begin_object();
+        self.parse_whitespace()?;
 
-        let mut map = <HashMap<String, JsonValue>>::new();
+        let mut members = Members::new(self.opts.duplicate_keys);
 
-        match self.peek().ok_or(JsonError::UnexpectedEnd)? {
-            '}' => {
+        match self.peek_or_end(EXPECT_KEY_OR_BRACE)? {
+            b'}' => {
                 self.consume();
-                return Ok(JsonValue::Object(map));
-            }
-            _ => {
-                let (k, v) = self.parse_kv()?;
-                map.insert(k, v);
+                builder.end_object();
+                return Ok(());
             }
+            _ => self.parse_member(&mut members, builder)?,
         };
 
         loop {
-            match self.consume().ok_or(JsonError::UnexpectedEnd)? {
-                ',' => {
-                    let (k, v) = self.parse_kv()?;
-                    map.insert(k, v);
+            match self.peek_or_end(EXPECT_COMMA_OR_BRACE)? {
+                b',' => {
+                    self.consume();
+                    self.parse_member(&mut members, builder)?;
+                }
+                b'}' => {
+                    self.consume();
+                    builder.end_object();
+                    return Ok(());
                 }
-                '}' => {
-                    return Ok(JsonValue::Object(map));
+                _ => {
+                    return Err(self.error_expecting(
+                        JsonErrorKind::ExpectedCommaOrBrace,
+                        EXPECT_COMMA_OR_BRACE,
+                    ))
                 }
-                _ => return Err(JsonError::InvalidValue),
             }
         }
     }
@@ -0,0 +1,300 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum JsonErrorKind {
+    RootNotSingular,
+    InvalidValue,
+    InvalidLiteral,
+    InvalidNumber,
+    NumberTooBig,
+    NumberUnderflow,
+    IntegerPrecisionLoss,
+    DecimalRounding,
+    InvalidEscape,
+    InvalidUnicodeEscape,
+    LoneSurrogate,
+    ControlCharacterInString,
+    TrailingCharacters,
+    TrailingComma,
+    InvalidComment,
+    ExpectedObjectKey,
+    DuplicateKey,
+    MissingColon,
+    ExpectedCommaOrBracket,
+    ExpectedCommaOrBrace,
+    DepthLimitExceeded,
+    InputTooLarge,
+    StringTooLong,
+    NumberTooLong,
+    TooManyElements,
+    TooManyMembers,
+    UnexpectedEnd,
+    InvalidUtf8,
+    Io,
+}
+
+impl JsonErrorKind {
+    pub fn message(self) -> &'static str {
+        match self {
+            JsonErrorKind::RootNotSingular => "unexpected content after root value",
+            JsonErrorKind::InvalidValue => "invalid value",
+            JsonErrorKind::InvalidLiteral => "invalid literal",
+            JsonErrorKind::InvalidNumber => "invalid number",
+            JsonErrorKind::NumberTooBig => "number too big",
+            JsonErrorKind::NumberUnderflow => "number underflows to zero",
+            JsonErrorKind::IntegerPrecisionLoss => "integer too large to represent exactly",
+            JsonErrorKind::DecimalRounding => "number rounded to nearest float",
+            JsonErrorKind::InvalidEscape => "invalid escape sequence",
+            JsonErrorKind::InvalidUnicodeEscape => "invalid unicode escape sequence",
+            JsonErrorKind::LoneSurrogate => "lone surrogate in unicode escape sequence",
+            JsonErrorKind::ControlCharacterInString => "control character in string",
+            JsonErrorKind::TrailingCharacters => "unexpected characters after value",
+            JsonErrorKind::TrailingComma => "trailing comma",
+            JsonErrorKind::InvalidComment => "invalid comment",
+            JsonErrorKind::ExpectedObjectKey => "invalid object key",
+            JsonErrorKind::DuplicateKey => "duplicate object key",
+            JsonErrorKind::MissingColon => "missing colon after object key",
+            JsonErrorKind::ExpectedCommaOrBracket => "unexpected character after array element",
+            JsonErrorKind::ExpectedCommaOrBrace => "unexpected character after object member",
+            JsonErrorKind::DepthLimitExceeded => "nesting depth limit exceeded",
+            JsonErrorKind::InputTooLarge => "input size limit exceeded",
+            JsonErrorKind::StringTooLong => "string length limit exceeded",
+            JsonErrorKind::NumberTooLong => "number length limit exceeded",
+            JsonErrorKind::TooManyElements => "array element count limit exceeded",
+            JsonErrorKind::TooManyMembers => "object member count limit exceeded",
+            JsonErrorKind::UnexpectedEnd => "unexpected end of input",
+            JsonErrorKind::InvalidUtf8 => "invalid UTF-8",
+            JsonErrorKind::Io => "i/o error",
+        }
+    }
+}
+
+impl fmt::Display for JsonErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+/// A location in the source text.
+///
+/// `offset` is a 0-based byte offset, `line` and `column` are 1-based and
+/// `column` counts chars.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn start() -> Self {
+        Self {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {} column {}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JsonError {
+    kind: JsonErrorKind,
+    position: Position,
+    expected: &'static [&'static str],
+    path: String,
+    io: Option<Arc<io::Error>>,
+}
+
+pub type JsonResult<T> = Result<T, JsonError>;
+
+impl JsonError {
+    pub fn new(kind: JsonErrorKind, position: Position) -> Self {
+        Self::with_expected(kind, position, &[])
+    }
+
+    pub fn with_expected(
+        kind: JsonErrorKind,
+        position: Position,
+        expected: &'static [&'static str],
+    ) -> Self {
+        Self {
+            kind,
+            position,
+            expected,
+            path: String::new(),
+            io: None,
+        }
+    }
+
+    /// Creates a `JsonErrorKind::Io` error for a read that failed after
+    /// `position`.
+    pub fn io(err: io::Error, position: Position) -> Self {
+        Self {
+            io: Some(Arc::new(err)),
+            ..Self::new(JsonErrorKind::Io, position)
+        }
+    }
+
+    pub fn with_path(mut self, path: String) -> Self {
+        self.path = path;
+        self
+    }
+
+    pub fn kind(&self) -> JsonErrorKind {
+        self.kind
+    }
+
+    /// The tokens that would have been accepted at the error position.
+    ///
+    /// Empty when the parser has no useful suggestion.
+    pub fn expected(&self) -> &'static [&'static str] {
+        self.expected
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn offset(&self) -> usize {
+        self.position.offset
+    }
+
+    pub fn line(&self) -> usize {
+        self.position.line
+    }
+
+    pub fn column(&self) -> usize {
+        self.position.column
+    }
+
+    /// The JSON Pointer (RFC 6901) of the value being parsed when the error
+    /// occurred, e.g. `/services/3/ports/1`.
+    ///
+    /// Empty when the error is located at the root value.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The underlying error of a `JsonErrorKind::Io` error.
+    pub fn io_error(&self) -> Option<&io::Error> {
+        self.io.as_deref()
+    }
+}
+
+/// Errors are equal when everything but the message of an underlying
+/// `io::Error`, which cannot be compared, is equal.
+impl PartialEq for JsonError {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
+            && self.position == other.position
+            && self.expected == other.expected
+            && self.path == other.path
+            && self.io.as_ref().map(|e| e.kind()) == other.io.as_ref().map(|e| e.kind())
+    }
+}
+
+impl Eq for JsonError {}
+
+impl JsonError {
+    fn fmt_message(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(ref err) = self.io {
+            write!(f, ": {}", err)?;
+        }
+        if let Some((last, init)) = self.expected.split_last() {
+            f.write_str(", expected ")?;
+            if !init.is_empty() {
+                write!(f, "{} or ", init.join(", "))?;
+            }
+            f.write_str(last)?;
+        }
+        Ok(())
+    }
+
+    /// Renders the error as a compiler-style diagnostic, showing the
+    /// offending line of `src` with a caret under the failing column.
+    ///
+    /// `src` must be the text that produced this error.
+    pub fn render(&self, src: &str) -> String {
+        self.render_from(src, 0)
+    }
+
+    /// Renders the error like `render`, given only the part of the text
+    /// from byte `start` on, such as the end of a stream kept while
+    /// parsing it. A line cut off by `start` is shown from `start` on.
+    pub fn render_from(&self, src: &str, start: usize) -> String {
+        Diagnostic {
+            err: self,
+            src,
+            start,
+        }
+        .to_string()
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_message(f)?;
+        write!(f, " at {}", self.position)?;
+        if !self.path.is_empty() {
+            write!(f, " in {}", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+struct Diagnostic<'a> {
+    err: &'a JsonError,
+    src: &'a str,
+    /// The offset in the whole text of the start of `src`.
+    start: usize,
+}
+
+impl fmt::Display for Diagnostic<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pos = self.err.position;
+        let offset = pos.offset.saturating_sub(self.start).min(self.src.len());
+        let line_start = self.src[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = self.src[offset..]
+            .find('\n')
+            .map_or(self.src.len(), |i| offset + i);
+        let line = self.src[line_start..line_end].trim_end_matches('\r');
+        // the line may be cut off, so the caret is placed by the offset
+        let before = self.src[line_start..offset].chars().count();
+
+        let line_no = pos.line.to_string();
+        let gutter = " ".repeat(line_no.len());
+
+        f.write_str("error: ")?;
+        self.err.fmt_message(f)?;
+        writeln!(f)?;
+        writeln!(f, "{}--> {}", gutter, pos)?;
+        writeln!(f, "{} |", gutter)?;
+        writeln!(f, "{} | {}", line_no, line)?;
+        write!(f, "{} | ", gutter)?;
+        // keep tabs so that the caret lines up with the source line
+        for ch in line.chars().take(before) {
+            f.write_str(if ch == '\t' { "\t" } else { " " })?;
+        }
+        f.write_str("^")?;
+        if !self.err.path.is_empty() {
+            write!(f, "\n{} = path: {}", gutter, self.err.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for JsonError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.io.as_deref().map(|err| err as &(dyn Error + 'static))
+    }
+}
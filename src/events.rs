@@ -0,0 +1,53 @@
+use crate::ctx::{EventState, JsonContext};
+use crate::error::JsonResult;
+use crate::number::Number;
+
+/// A piece of a JSON document, as produced by `JsonEvents`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum JsonEvent {
+    StartObject,
+    EndObject,
+    StartArray,
+    EndArray,
+    /// The key of the object member whose value comes next.
+    Key(String),
+    Null,
+    Boolean(bool),
+    Number(Number),
+    String(String),
+    /// A string holding lone surrogates, encoded as WTF-8.
+    ///
+    /// Only produced when parsing with `SurrogatePolicy::Preserve`. Bytes
+    /// that are not valid WTF-8 are stringified as U+FFFD once collected
+    /// into a `JsonValue::Wtf8String`.
+    Wtf8String(Vec<u8>),
+}
+
+/// A pull parser yielding the events of a single JSON document in order,
+/// without building a `JsonValue`.
+///
+/// Only the open containers and the current token are held in memory. The
+/// first error ends the iteration. Every member is reported as it occurs,
+/// so `DuplicateKeyPolicy::Reject` is the only duplicate key policy that
+/// has an effect, and `LossyNumberPolicy::Warn` has none.
+pub struct JsonEvents<'a> {
+    ctx: JsonContext<'a>,
+    state: EventState,
+}
+
+impl<'a> JsonEvents<'a> {
+    pub(crate) fn new(ctx: JsonContext<'a>) -> Self {
+        Self {
+            ctx,
+            state: EventState::new(),
+        }
+    }
+}
+
+impl Iterator for JsonEvents<'_> {
+    type Item = JsonResult<JsonEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.state.next_event(&mut self.ctx).transpose()
+    }
+}
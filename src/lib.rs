@@ -1,26 +1,164 @@
 #[macro_use]
 extern crate lazy_static;
 
+#[macro_use]
+mod trace;
+
+mod builder;
 mod ctx;
+mod error;
+mod events;
+mod number;
+mod options;
+mod push;
+mod reader;
 mod types;
 mod validate;
+mod value_ref;
 
-pub use self::types::{JsonError, JsonResult, JsonValue};
+pub use self::builder::{JsonBuilder, JsonValueBuilder};
+pub use self::error::{JsonError, JsonErrorKind, JsonResult, Position};
+pub use self::events::{JsonEvent, JsonEvents};
+pub use self::number::Number;
+pub use self::options::{
+    Dialect, DuplicateKeyPolicy, EscapeMode, LossyNumberPolicy, ParseMode, ParseOptions,
+    StringifyOptions, SurrogatePolicy,
+};
+pub use self::push::JsonPushParser;
+pub use self::types::{JsonMap, JsonValue};
+pub use self::value_ref::{JsonMapRef, JsonValueRef};
 
+use self::builder::TreeBuilder;
 use self::ctx::JsonContext;
 
+use std::borrow::Cow;
+use std::io::Read;
+
 pub fn parse(src: &str) -> JsonResult<JsonValue> {
-    let mut ctx = JsonContext::new(src);
+    parse_with(src, &ParseOptions::default())
+}
+
+pub fn parse_with(src: &str, opts: &ParseOptions) -> JsonResult<JsonValue> {
+    parse_with_warnings(src, opts).map(|(val, _)| val)
+}
+
+/// Parses like `parse_with`, also returning the non-fatal problems found
+/// along the way, such as lossy numbers under `LossyNumberPolicy::Warn`.
+pub fn parse_with_warnings(
+    src: &str,
+    opts: &ParseOptions,
+) -> JsonResult<(JsonValue, Vec<JsonError>)> {
+    let mut ctx = JsonContext::new(src, opts);
+    if let Some(err) = ctx.take_input_error() {
+        return Err(err);
+    }
+
+    let val = parse_root(&mut ctx, opts, JsonValueBuilder::new(opts.duplicate_keys))?;
+    Ok((val, ctx.into_warnings()))
+}
 
-    let val = ctx.parse_value()?;
+pub fn parse_borrowed(src: &str) -> JsonResult<JsonValueRef<'_>> {
+    parse_borrowed_with(src, &ParseOptions::default())
+}
+
+/// Parses like `parse_with`, borrowing every string and object key that
+/// has no escapes from `src` instead of copying it.
+pub fn parse_borrowed_with<'a>(src: &'a str, opts: &ParseOptions) -> JsonResult<JsonValueRef<'a>> {
+    let mut ctx = JsonContext::new(src, opts);
+    if let Some(err) = ctx.take_input_error() {
+        return Err(err);
+    }
+
+    let builder = TreeBuilder::<Cow<'a, str>, JsonValueRef<'a>>::new(opts.duplicate_keys);
+    parse_root(&mut ctx, opts, builder)
+}
+
+pub fn parse_into<'a, B: JsonBuilder<'a>>(src: &'a str, builder: B) -> JsonResult<B::Output> {
+    parse_into_with(src, &ParseOptions::default(), builder)
+}
+
+/// Parses `src` by passing its values to `builder` as they are read, and
+/// returns what the builder makes of them, see `JsonBuilder`.
+pub fn parse_into_with<'a, B: JsonBuilder<'a>>(
+    src: &'a str,
+    opts: &ParseOptions,
+    builder: B,
+) -> JsonResult<B::Output> {
+    let mut ctx = JsonContext::new(src, opts);
+    if let Some(err) = ctx.take_input_error() {
+        return Err(err);
+    }
+
+    parse_root(&mut ctx, opts, builder)
+}
+
+pub fn parse_reader<R: Read>(reader: R) -> JsonResult<JsonValue> {
+    parse_reader_with(reader, &ParseOptions::default())
+}
+
+/// Parses UTF-8 text read from `reader` in chunks, keeping only the part of
+/// the input that has not been parsed yet in memory.
+///
+/// `reader` does not need to be buffered. Read failures are reported as
+/// `JsonErrorKind::Io` and input that is not UTF-8 as
+/// `JsonErrorKind::InvalidUtf8`; `max_input_len` is checked as the input
+/// is read.
+pub fn parse_reader_with<R: Read>(mut reader: R, opts: &ParseOptions) -> JsonResult<JsonValue> {
+    let mut ctx = JsonContext::from_reader(&mut reader, opts);
+    let res = parse_root(&mut ctx, opts, JsonValueBuilder::new(opts.duplicate_keys));
+    match ctx.take_input_error() {
+        Some(err) => Err(err),
+        None => res,
+    }
+}
+
+pub fn events(src: &str) -> JsonEvents<'_> {
+    events_with(src, &ParseOptions::default())
+}
+
+/// Creates a pull parser over `src`, see `JsonEvents`. `ParseOptions::mode`
+/// does not apply, as events never recurse.
+pub fn events_with<'a>(src: &'a str, opts: &ParseOptions) -> JsonEvents<'a> {
+    JsonEvents::new(JsonContext::new(src, opts))
+}
+
+/// Creates a pull parser reading from `reader` in chunks, like
+/// `parse_reader_with`.
+pub fn events_reader_with<'a, R: Read>(reader: &'a mut R, opts: &ParseOptions) -> JsonEvents<'a> {
+    JsonEvents::new(JsonContext::from_reader(reader, opts))
+}
+
+pub fn push_parser() -> JsonPushParser {
+    push_parser_with(&ParseOptions::default())
+}
+
+/// Creates a parser for input that is pushed in pieces, see
+/// `JsonPushParser`.
+pub fn push_parser_with(opts: &ParseOptions) -> JsonPushParser {
+    JsonPushParser::new(opts)
+}
+
+fn parse_root<'a, B: JsonBuilder<'a>>(
+    ctx: &mut JsonContext<'a>,
+    opts: &ParseOptions,
+    mut builder: B,
+) -> JsonResult<B::Output> {
+    match opts.mode {
+        ParseMode::Recursive => ctx.parse_value(&mut builder)?,
+        ParseMode::Iterative => ctx.parse_value_iterative(&mut builder)?,
+    };
 
     if ctx.peek().is_none() {
-        Ok(val)
+        Ok(builder.finish())
     } else {
-        Err(JsonError::RootNotSingular)
+        Err(ctx.error(JsonErrorKind::RootNotSingular))
     }
 }
 
 pub fn stringify(value: &JsonValue) -> String {
     value.stringify()
 }
+
+pub fn stringify_with(value: &JsonValue, opts: &StringifyOptions) -> String {
+    value.stringify_with(opts)
+}
@@ -0,0 +1,232 @@
+use crate::error::JsonErrorKind;
+use crate::validate::validate_number;
+
+use std::fmt;
+
+/// A JSON number.
+///
+/// Remembers whether it was written as an unsigned integer, a negative
+/// integer or a float, so that integers outside the exact range of `f64`
+/// (above 2^53) keep every digit. Integers that do not fit in 64 bits are
+/// stored as floats.
+///
+/// A number can also hold its literal text verbatim, see
+/// `ParseOptions::arbitrary_precision` and `Number::from_literal`.
+#[derive(Clone, PartialEq)]
+pub struct Number(N);
+
+#[derive(Clone, PartialEq)]
+enum N {
+    PosInt(u64),
+    /// Always less than zero.
+    NegInt(i64),
+    Float(f64),
+    /// A valid JSON number literal, kept as written.
+    Literal(Box<str>),
+}
+
+impl Number {
+    /// Converts an integer literal that has passed number validation,
+    /// falling back to a float when it does not fit in 64 bits.
+    ///
+    /// `-0` is kept as the float `-0.0` so that its sign survives.
+    pub(crate) fn from_int_literal(s: &str) -> Option<Self> {
+        let n = if s.starts_with('-') {
+            match s.parse::<i64>().ok()? {
+                0 => return None,
+                n => N::NegInt(n),
+            }
+        } else {
+            N::PosInt(s.parse().ok()?)
+        };
+        Some(Number(n))
+    }
+
+    /// Wraps a literal that has passed number validation.
+    pub(crate) fn from_literal_unchecked(s: &str) -> Self {
+        Number(N::Literal(s.into()))
+    }
+
+    /// Creates a number that keeps `s` verbatim, e.g. `1.10` or `1e400`.
+    ///
+    /// Returns `None` if `s` is not a valid JSON number literal.
+    pub fn from_literal(s: &str) -> Option<Self> {
+        if validate_number(s) {
+            Some(Number::from_literal_unchecked(s))
+        } else {
+            None
+        }
+    }
+
+    /// The literal text, if the number was kept verbatim.
+    pub fn as_literal(&self) -> Option<&str> {
+        match self.0 {
+            N::Literal(ref s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn is_u64(&self) -> bool {
+        self.as_u64().is_some()
+    }
+
+    pub fn is_i64(&self) -> bool {
+        self.as_i64().is_some()
+    }
+
+    /// Returns `true` unless the number is an integer that fits in a
+    /// `u64` or an `i64`.
+    pub fn is_f64(&self) -> bool {
+        match self.0 {
+            N::Float(_) => true,
+            N::Literal(_) => !self.is_u64() && !self.is_i64(),
+            N::PosInt(_) | N::NegInt(_) => false,
+        }
+    }
+
+    /// Returns the value if it is an integer that fits in a `u64`.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.0 {
+            N::PosInt(n) => Some(n),
+            N::Literal(ref s) => Number::from_int_literal(s)?.as_u64(),
+            N::NegInt(_) | N::Float(_) => None,
+        }
+    }
+
+    /// Returns the value if it is an integer that fits in an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.0 {
+            N::PosInt(n) if n <= i64::MAX as u64 => Some(n as i64),
+            N::NegInt(n) => Some(n),
+            N::Literal(ref s) => Number::from_int_literal(s)?.as_i64(),
+            _ => None,
+        }
+    }
+
+    /// Returns `false` for an infinite or NaN float. A literal is always
+    /// finite, even if it is outside the range of `f64`.
+    pub fn is_finite(&self) -> bool {
+        match self.0 {
+            N::Float(n) => n.is_finite(),
+            _ => true,
+        }
+    }
+
+    /// Returns the value as a float, rounding integers above 2^53.
+    ///
+    /// A literal outside the range of `f64` gives an infinity.
+    pub fn as_f64(&self) -> f64 {
+        match self.0 {
+            N::PosInt(n) => n as f64,
+            N::NegInt(n) => n as f64,
+            N::Float(n) => n,
+            N::Literal(ref s) => s.parse().expect("illegal float number"),
+        }
+    }
+}
+
+/// Splits a number literal or the `{:e}` output of a float into its
+/// significant digits and decimal exponent, without leading or trailing
+/// zeros. Returns `None` for zero.
+fn decimal_digits(s: &str) -> Option<(String, i64)> {
+    let s = s.trim_start_matches(['-', '+']);
+    let (mantissa, exp) = match s.find(['e', 'E']) {
+        Some(i) => (&s[..i], s[i + 1..].parse::<i64>().unwrap_or(0)),
+        None => (s, 0),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", int, frac);
+    let digits = digits.trim_start_matches('0');
+    let trimmed = digits.trim_end_matches('0');
+    if trimmed.is_empty() {
+        return None;
+    }
+    let exp = exp - frac.len() as i64 + (digits.len() - trimmed.len()) as i64;
+    Some((trimmed.to_owned(), exp))
+}
+
+/// Checks whether converting the validated literal `s` to `num` lost
+/// information, returning the kind of loss.
+///
+/// Integer literals must convert exactly, while other literals only need
+/// to match the shortest text that reads back as `num`.
+pub(crate) fn conversion_loss(s: &str, num: f64) -> Option<JsonErrorKind> {
+    let literal = decimal_digits(s)?;
+    if num == 0.0 {
+        return Some(JsonErrorKind::NumberUnderflow);
+    }
+    if s.contains(['.', 'e', 'E']) {
+        if decimal_digits(&format!("{:e}", num)).as_ref() != Some(&literal) {
+            return Some(JsonErrorKind::DecimalRounding);
+        }
+    } else if decimal_digits(&format!("{:.0}", num)).as_ref() != Some(&literal) {
+        return Some(JsonErrorKind::IntegerPrecisionLoss);
+    }
+    None
+}
+
+macro_rules! impl_from_unsigned {
+    ($($ty:ty),*) => {$(
+        impl From<$ty> for Number {
+            fn from(n: $ty) -> Self {
+                Number(N::PosInt(n as u64))
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_from_signed {
+    ($($ty:ty),*) => {$(
+        impl From<$ty> for Number {
+            fn from(n: $ty) -> Self {
+                if n < 0 {
+                    Number(N::NegInt(n as i64))
+                } else {
+                    Number(N::PosInt(n as u64))
+                }
+            }
+        }
+    )*};
+}
+
+impl_from_unsigned!(u8, u16, u32, u64, usize);
+impl_from_signed!(i8, i16, i32, i64, isize);
+
+impl From<f32> for Number {
+    fn from(n: f32) -> Self {
+        Number(N::Float(n.into()))
+    }
+}
+
+impl From<f64> for Number {
+    fn from(n: f64) -> Self {
+        Number(N::Float(n))
+    }
+}
+
+impl fmt::Debug for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            N::PosInt(n) => fmt::Debug::fmt(&n, f),
+            N::NegInt(n) => fmt::Debug::fmt(&n, f),
+            N::Float(n) => fmt::Debug::fmt(&n, f),
+            N::Literal(ref s) => f.write_str(s),
+        }
+    }
+}
+
+/// Writes floats so that they read back as floats, as in `1.0` or
+/// `1e300`; non-finite floats are written as `inf`, `-inf` and `NaN`.
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            N::PosInt(n) => fmt::Display::fmt(&n, f),
+            N::NegInt(n) => fmt::Display::fmt(&n, f),
+            // unlike `Display`, `Debug` keeps the fraction of integral
+            // floats and switches to exponents for large and small ones
+            N::Float(n) if n.is_finite() => fmt::Debug::fmt(&n, f),
+            N::Float(n) => fmt::Display::fmt(&n, f),
+            N::Literal(ref s) => f.write_str(s),
+        }
+    }
+}
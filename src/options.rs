@@ -0,0 +1,268 @@
+/// What to do with a `\u` escape that encodes a UTF-16 surrogate which is
+/// not part of a valid surrogate pair.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum SurrogatePolicy {
+    /// Fail with `JsonErrorKind::LoneSurrogate`.
+    #[default]
+    Reject,
+    /// Replace the surrogate with U+FFFD REPLACEMENT CHARACTER.
+    Replace,
+    /// Keep the surrogate by encoding the string as WTF-8, producing a
+    /// `JsonValue::Wtf8String` that `stringify` writes back unchanged.
+    ///
+    /// Object keys must be valid UTF-8, so lone surrogates in keys are
+    /// still rejected.
+    Preserve,
+}
+
+/// What to do when an object contains the same key more than once.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum DuplicateKeyPolicy {
+    /// Fail with `JsonErrorKind::DuplicateKey`, located at the repeated key
+    /// and with the key as the last segment of the error path.
+    Reject,
+    /// Keep the value of the first occurrence.
+    KeepFirst,
+    /// Keep the value of the last occurrence, like most JSON parsers do.
+    /// The member keeps the position of the first occurrence.
+    #[default]
+    KeepLast,
+    /// Map a repeated key to an array holding the values of all its
+    /// occurrences in source order. Keys that occur once are unaffected.
+    CollectAll,
+}
+
+/// What to do when a number literal cannot be represented exactly.
+///
+/// Detects literals that underflow to zero
+/// (`JsonErrorKind::NumberUnderflow`), integers beyond 64 bits that do not
+/// fit in an `f64` (`JsonErrorKind::IntegerPrecisionLoss`) and decimals
+/// with more significant digits than an `f64` holds
+/// (`JsonErrorKind::DecimalRounding`). Decimals like `0.1`, which read back
+/// to the same text, are not considered lossy.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum LossyNumberPolicy {
+    /// Convert silently.
+    #[default]
+    Allow,
+    /// Convert and record a warning, see `parse_with_warnings`.
+    Warn,
+    /// Fail with the kind of loss.
+    Reject,
+}
+
+/// How the parser descends into arrays and objects.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ParseMode {
+    /// Recurse once per nesting level. Fast, but the nesting depth must be
+    /// bounded by `max_depth` to stay within the thread's stack.
+    #[default]
+    Recursive,
+    /// Keep open arrays and objects on a heap-allocated stack, so that
+    /// nesting is limited only by memory when `max_depth` is `None`.
+    Iterative,
+}
+
+/// The grammar the parser accepts.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Dialect {
+    /// Strict JSON as specified by RFC 8259.
+    #[default]
+    Json,
+    /// JSON with comments, as used by `tsconfig.json` or VS Code settings:
+    /// strict JSON that also allows `//` and `/* */` comments and trailing
+    /// commas.
+    Jsonc,
+    /// JSON5, which adds comments, trailing commas, unquoted identifier
+    /// keys, single-quoted and multi-line strings, more escapes, hex
+    /// numbers, leading and trailing decimal points, a leading `+` sign,
+    /// `Infinity`, `NaN` and more whitespace chars.
+    ///
+    /// `Infinity` and `NaN` become non-finite floats, which `stringify`
+    /// writes as `null`. Hex literals must fit in a `u64`, or an `i64` when
+    /// negative, and are never kept verbatim by
+    /// `ParseOptions::arbitrary_precision`, nor are other numbers that are
+    /// not valid JSON.
+    ///
+    /// Unquoted keys follow the ES5 IdentifierName grammar, including
+    /// `\uXXXX` escapes, with the Unicode XID_Start and XID_Continue
+    /// properties standing in for its char categories.
+    Json5,
+}
+
+impl Dialect {
+    /// Whether `//` and `/* */` comments count as whitespace.
+    pub(crate) fn comments(self) -> bool {
+        self != Dialect::Json
+    }
+
+    /// Whether a comma may follow the last element of an array or the last
+    /// member of an object.
+    pub(crate) fn trailing_commas(self) -> bool {
+        self != Dialect::Json
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ParseOptions {
+    pub(crate) dialect: Dialect,
+    pub(crate) lone_surrogates: SurrogatePolicy,
+    pub(crate) duplicate_keys: DuplicateKeyPolicy,
+    pub(crate) arbitrary_precision: bool,
+    pub(crate) lossy_numbers: LossyNumberPolicy,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) mode: ParseMode,
+    pub(crate) max_input_len: Option<usize>,
+    pub(crate) max_string_len: Option<usize>,
+    pub(crate) max_number_len: Option<usize>,
+    pub(crate) max_array_len: Option<usize>,
+    pub(crate) max_object_len: Option<usize>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            dialect: Dialect::default(),
+            lone_surrogates: SurrogatePolicy::default(),
+            duplicate_keys: DuplicateKeyPolicy::default(),
+            arbitrary_precision: false,
+            lossy_numbers: LossyNumberPolicy::default(),
+            max_depth: Some(ParseOptions::DEFAULT_MAX_DEPTH),
+            mode: ParseMode::default(),
+            max_input_len: None,
+            max_string_len: None,
+            max_number_len: None,
+            max_array_len: None,
+            max_object_len: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits how deeply arrays and objects may be nested, failing with
+    /// `JsonErrorKind::DepthLimitExceeded` beyond it.
+    ///
+    /// Defaults to `Some(DEFAULT_MAX_DEPTH)`. In `ParseMode::Recursive`
+    /// the parser recurses once per nesting level, so `None` lets hostile
+    /// input overflow the stack.
+    pub fn max_depth(mut self, depth: Option<usize>) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Limits the size of the whole input in bytes, failing with
+    /// `JsonErrorKind::InputTooLarge` before parsing starts.
+    pub fn max_input_len(mut self, len: Option<usize>) -> Self {
+        self.max_input_len = len;
+        self
+    }
+
+    /// Limits the decoded length in bytes of every string and object key,
+    /// failing with `JsonErrorKind::StringTooLong`.
+    pub fn max_string_len(mut self, len: Option<usize>) -> Self {
+        self.max_string_len = len;
+        self
+    }
+
+    /// Limits the length of number literals, failing with
+    /// `JsonErrorKind::NumberTooLong`.
+    pub fn max_number_len(mut self, len: Option<usize>) -> Self {
+        self.max_number_len = len;
+        self
+    }
+
+    /// Limits the number of elements in every array, failing with
+    /// `JsonErrorKind::TooManyElements`.
+    pub fn max_array_len(mut self, len: Option<usize>) -> Self {
+        self.max_array_len = len;
+        self
+    }
+
+    /// Limits the number of members in every object, failing with
+    /// `JsonErrorKind::TooManyMembers`.
+    pub fn max_object_len(mut self, len: Option<usize>) -> Self {
+        self.max_object_len = len;
+        self
+    }
+
+    /// Defaults to `Dialect::Json`.
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    pub fn mode(mut self, mode: ParseMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn lone_surrogates(mut self, policy: SurrogatePolicy) -> Self {
+        self.lone_surrogates = policy;
+        self
+    }
+
+    /// Defaults to `DuplicateKeyPolicy::KeepLast`.
+    pub fn duplicate_keys(mut self, policy: DuplicateKeyPolicy) -> Self {
+        self.duplicate_keys = policy;
+        self
+    }
+
+    /// Keeps the text of every number literal verbatim, so that `1.10`,
+    /// `1e400` or a 30-digit decimal stringify exactly as they were
+    /// written. Numbers out of the range of `f64` are then accepted
+    /// instead of failing with `JsonErrorKind::NumberTooBig`.
+    ///
+    /// Defaults to `false`.
+    pub fn arbitrary_precision(mut self, enabled: bool) -> Self {
+        self.arbitrary_precision = enabled;
+        self
+    }
+
+    /// Defaults to `LossyNumberPolicy::Allow`. Has no effect together with
+    /// `arbitrary_precision`, which never converts.
+    pub fn lossy_numbers(mut self, policy: LossyNumberPolicy) -> Self {
+        self.lossy_numbers = policy;
+        self
+    }
+}
+
+/// Which characters `stringify` writes as `\u` escapes, besides those JSON
+/// always requires to be escaped.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum EscapeMode {
+    /// Escape only what JSON requires: quotes, backslashes and control
+    /// characters.
+    #[default]
+    Minimal,
+    /// Escape every non-ASCII character, using surrogate pairs for
+    /// characters outside the Basic Multilingual Plane.
+    AsciiOnly,
+    /// Escape `<`, `>`, `&` and `'` as well as U+2028 and U+2029, so that
+    /// the output can be embedded in HTML, including `<script>` elements.
+    HtmlSafe,
+    /// Escape U+2028 LINE SEPARATOR and U+2029 PARAGRAPH SEPARATOR, which
+    /// are line terminators in JavaScript source before ES2019.
+    JsSafe,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct StringifyOptions {
+    pub(crate) escape: EscapeMode,
+}
+
+impl StringifyOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn escape(mut self, mode: EscapeMode) -> Self {
+        self.escape = mode;
+        self
+    }
+}
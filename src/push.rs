@@ -0,0 +1,75 @@
+use crate::ctx::{EventState, JsonContext};
+use crate::error::{JsonError, JsonResult};
+use crate::events::JsonEvent;
+use crate::options::ParseOptions;
+
+/// A push parser for a single JSON document that arrives in pieces, such
+/// as a request body read from a socket.
+///
+/// Each call to `feed` parses as far as the input allows and returns the
+/// events completed so far. A string, number or literal cut off by the end
+/// of a piece is kept half parsed and finished by the next one, so no byte
+/// is parsed twice; pieces may also split UTF-8 chars. `finish` marks the
+/// end of the input.
+///
+/// Produces the same events and errors as `JsonEvents`. The first error
+/// ends parsing; it is returned by the call after the one that returns the
+/// events before it, if any, and again by every later call. `finish`
+/// returns the error even if events come before it, so that input cut off
+/// by its end never looks like a complete document.
+pub struct JsonPushParser {
+    ctx: JsonContext<'static>,
+    state: EventState,
+    error: Option<JsonError>,
+}
+
+impl JsonPushParser {
+    pub(crate) fn new(opts: &ParseOptions) -> Self {
+        Self {
+            ctx: JsonContext::for_push(opts),
+            state: EventState::new(),
+            error: None,
+        }
+    }
+
+    /// Parses the next piece of input, returning the events it completes.
+    pub fn feed(&mut self, chunk: &[u8]) -> JsonResult<Vec<JsonEvent>> {
+        if let Some(ref err) = self.error {
+            return Err(err.clone());
+        }
+        self.ctx.feed(chunk);
+        self.drain()
+    }
+
+    /// Ends the input, returning the events completed by its end, such as
+    /// a number at the root, or the error if the document is not complete.
+    pub fn finish(mut self) -> JsonResult<Vec<JsonEvent>> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        self.ctx.finish_input();
+        let events = self.drain();
+        match self.error {
+            Some(err) => Err(err),
+            None => events,
+        }
+    }
+
+    fn drain(&mut self) -> JsonResult<Vec<JsonEvent>> {
+        let mut events = Vec::new();
+        loop {
+            match self.state.next_event(&mut self.ctx) {
+                Ok(Some(event)) => events.push(event),
+                Ok(None) => return Ok(events),
+                Err(err) => {
+                    self.error = Some(err.clone());
+                    return if events.is_empty() {
+                        Err(err)
+                    } else {
+                        Ok(events)
+                    };
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::io::{self, Read};
+
+/// Size of the chunks read from the underlying reader.
+const CHUNK_LEN: usize = 8 * 1024;
+
+pub enum ReadError {
+    Io(io::Error),
+    /// The input is not UTF-8; the bad bytes start right after the valid
+    /// text already returned.
+    InvalidUtf8,
+}
+
+/// Checks UTF-8 text that arrives in pieces, holding back a char split
+/// across two pieces until it is complete.
+pub struct Utf8Chunks {
+    /// Bytes received but not yet returned, at most an incomplete char
+    /// between calls.
+    pending: Vec<u8>,
+    invalid: bool,
+}
+
+impl Utf8Chunks {
+    pub fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            invalid: false,
+        }
+    }
+
+    /// Whether bytes that are not UTF-8 have been received.
+    pub fn is_invalid(&self) -> bool {
+        self.invalid
+    }
+
+    /// Appends the whole chars completed by `bytes` to `buf`, returning how
+    /// many bytes were appended. Stops for good at the first invalid byte.
+    pub fn push(&mut self, bytes: &[u8], buf: &mut Vec<u8>) -> usize {
+        if self.invalid {
+            return 0;
+        }
+        self.pending.extend_from_slice(bytes);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            Err(e) => {
+                self.invalid = e.error_len().is_some();
+                e.valid_up_to()
+            }
+        };
+        buf.extend(self.pending.drain(..valid));
+        valid
+    }
+
+    /// Checks that the input has not ended inside a char.
+    pub fn finish(&self) -> Result<(), ReadError> {
+        if self.invalid || !self.pending.is_empty() {
+            Err(ReadError::InvalidUtf8)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Reads UTF-8 text in chunks, holding back a char split across two reads
+/// until it is complete.
+pub struct ReadInput<'a> {
+    inner: &'a mut dyn Read,
+    chunks: Utf8Chunks,
+}
+
+impl<'a> ReadInput<'a> {
+    pub fn new(inner: &'a mut dyn Read) -> Self {
+        Self {
+            inner,
+            chunks: Utf8Chunks::new(),
+        }
+    }
+
+    /// Appends the next run of whole chars to `buf`, returning how many
+    /// bytes were appended, or 0 at the end of input.
+    pub fn read_into(&mut self, buf: &mut Vec<u8>) -> Result<usize, ReadError> {
+        if self.chunks.is_invalid() {
+            return Err(ReadError::InvalidUtf8);
+        }
+        let mut chunk = [0; CHUNK_LEN];
+        loop {
+            let n = match self.inner.read(&mut chunk) {
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(ReadError::Io(e)),
+            };
+            if n == 0 {
+                // a char cut off by the end of input
+                return self.chunks.finish().map(|_| 0);
+            }
+
+            let valid = self.chunks.push(&chunk[..n], buf);
+            if valid > 0 {
+                return Ok(valid);
+            }
+            if self.chunks.is_invalid() {
+                return Err(ReadError::InvalidUtf8);
+            }
+        }
+    }
+}
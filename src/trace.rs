@@ -0,0 +1,32 @@
+//! Structured parser events, emitted through `tracing` or `log` when one of
+//! those cargo features is enabled and compiled out otherwise.
+//!
+//! Events use the `json_rs` target and carry an `event` name plus key-value
+//! fields, e.g. `value_start kind="array" offset=12`.
+
+#[cfg(feature = "tracing")]
+macro_rules! trace_event {
+    ($level:ident, $event:literal, $($key:ident = $value:expr),* $(,)?) => {
+        tracing::$level!(target: "json_rs", event = $event, $($key = $value),*)
+    };
+}
+
+#[cfg(all(feature = "log", not(feature = "tracing")))]
+macro_rules! trace_event {
+    ($level:ident, $event:literal, $($key:ident = $value:expr),* $(,)?) => {
+        log::$level!(
+            target: "json_rs",
+            concat!($event $(, " ", stringify!($key), "={:?}")*),
+            $($value),*
+        )
+    };
+}
+
+#[cfg(not(any(feature = "log", feature = "tracing")))]
+macro_rules! trace_event {
+    ($level:ident, $event:literal, $($key:ident = $value:expr),* $(,)?) => {
+        if false {
+            $(let _ = &$value;)*
+        }
+    };
+}
@@ -1,32 +1,62 @@
-use std::collections::HashMap;
+use crate::number::Number;
+use crate::options::{EscapeMode, StringifyOptions};
+
+use indexmap::IndexMap;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::Deref;
 use std::ops::Index;
 use std::ops::IndexMut;
-use std::string::ToString;
 
-#[derive(Debug, PartialEq)]
+/// The members of a JSON object, in source or insertion order.
+pub type JsonMap = IndexMap<String, JsonValue>;
+
+/// A parsed JSON value.
+///
+/// Dropping a value does not recurse, so values of any depth are safe to
+/// drop. As the type implements `Drop`, a pattern match cannot move the
+/// contents out of it; use `into_array`, `into_object`, `into_string` or
+/// `take` instead.
 pub enum JsonValue {
     Null,
     Boolean(bool),
-    Number(f64),
+    Number(Number),
     String(String),
+    /// A string holding lone surrogates, encoded as WTF-8.
+    ///
+    /// Only produced when parsing with `SurrogatePolicy::Preserve`. If one
+    /// is built from other bytes, `stringify` writes each sequence that is
+    /// not valid WTF-8 as U+FFFD REPLACEMENT CHARACTER.
+    Wtf8String(Vec<u8>),
     Array(Vec<JsonValue>),
-    Object(HashMap<String, JsonValue>),
+    Object(JsonMap),
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub enum JsonError {
-    RootNotSingular,
-    InvalidValue,
-    NumberTooBig,
-    MissingColon,
-    UnexpectedEnd,
-}
-
-pub type JsonResult<T> = Result<T, JsonError>;
-
 impl JsonValue {
-    fn stringify_string_raw(s: &str, buf: &mut String) {
+    fn stringify_string_raw(s: &str, buf: &mut String, mode: EscapeMode) {
         buf.push('\"');
+        JsonValue::escape_to_buf(s, buf, mode);
+        buf.push('\"')
+    }
+
+    fn push_hex4(unit: u16, buf: &mut String) {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        buf.push_str(r#"\u"#);
+        for shift in &[12, 8, 4, 0] {
+            buf.push(char::from(HEX[usize::from((unit >> shift) & 0xf)]));
+        }
+    }
+
+    fn needs_unicode_escape(ch: char, mode: EscapeMode) -> bool {
+        match mode {
+            EscapeMode::Minimal => false,
+            EscapeMode::AsciiOnly => !ch.is_ascii(),
+            EscapeMode::HtmlSafe => matches!(ch, '<' | '>' | '&' | '\'' | '\u{2028}' | '\u{2029}'),
+            EscapeMode::JsSafe => matches!(ch, '\u{2028}' | '\u{2029}'),
+        }
+    }
+
+    fn escape_to_buf(s: &str, buf: &mut String, mode: EscapeMode) {
         for ch in s.chars() {
             match ch {
                 '\"' => buf.push_str(r#"\""#),
@@ -36,60 +66,360 @@ impl JsonValue {
                 '\n' => buf.push_str(r#"\n"#),
                 '\r' => buf.push_str(r#"\r"#),
                 '\t' => buf.push_str(r#"\t"#),
-                ch if !is_unescaped_char(ch) => {
-                    buf.push_str(r#"\u"#);
-                    let mut t = ch as u32;
-                    for _ in 0..4 {
-                        let c = unsafe {
-                            std::char::from_u32_unchecked(match (t >> 12) & 0xf {
-                                t @ 0...10 => u32::from(b'0') + t,
-                                t @ 10...16 => u32::from(b'a') + (t - 10),
-                                _ => unreachable!(),
-                            })
-                        };
-                        buf.push(c);
-                        t >>= 4;
+                ch if !is_unescaped_char(ch) || JsonValue::needs_unicode_escape(ch, mode) => {
+                    for unit in ch.encode_utf16(&mut [0; 2]) {
+                        JsonValue::push_hex4(*unit, buf);
                     }
                 }
                 ch => buf.push(ch),
             }
         }
+    }
+
+    fn stringify_wtf8_raw(bytes: &[u8], buf: &mut String, mode: EscapeMode) {
+        buf.push('\"');
+        let mut rest = bytes;
+        while let Err(err) = std::str::from_utf8(rest) {
+            let (valid, invalid) = rest.split_at(err.valid_up_to());
+            let utf8 = unsafe { std::str::from_utf8_unchecked(valid) };
+            JsonValue::escape_to_buf(utf8, buf, mode);
+            rest = match *invalid {
+                // an encoded surrogate is the only sequence starting with
+                // 0xED followed by a continuation byte above 0x9F
+                [0xED, b1 @ 0xA0..=0xBF, b2 @ 0x80..=0xBF, ..] => {
+                    let unit = 0xD000 | (u16::from(b1 & 0x3F) << 6) | u16::from(b2 & 0x3F);
+                    JsonValue::push_hex4(unit, buf);
+                    &invalid[3..]
+                }
+                // not WTF-8 either, like `String::from_utf8_lossy`
+                _ => {
+                    JsonValue::escape_to_buf("\u{fffd}", buf, mode);
+                    &invalid[err.error_len().unwrap_or(invalid.len())..]
+                }
+            };
+        }
+        // the loop ends once the rest is valid UTF-8
+        let utf8 = unsafe { std::str::from_utf8_unchecked(rest) };
+        JsonValue::escape_to_buf(utf8, buf, mode);
         buf.push('\"')
     }
 
-    fn stringify_to_buf(&self, buf: &mut String) {
+    fn stringify_leaf(&self, buf: &mut String, opts: &StringifyOptions) {
         match self {
             JsonValue::Null => buf.push_str("null"),
             JsonValue::Boolean(true) => buf.push_str("true"),
             JsonValue::Boolean(false) => buf.push_str("false"),
+            // like `JSON.stringify`, as JSON has no infinities or NaN
+            JsonValue::Number(num) if !num.is_finite() => buf.push_str("null"),
             JsonValue::Number(num) => buf.push_str(&num.to_string()),
-            JsonValue::String(ref s) => JsonValue::stringify_string_raw(s, buf),
-            JsonValue::Array(ref arr) => {
-                buf.push('[');
-                if let Some(first) = arr.first() {
-                    first.stringify_to_buf(buf);
-                    for val in &arr[1..] {
+            JsonValue::String(ref s) => JsonValue::stringify_string_raw(s, buf, opts.escape),
+            JsonValue::Wtf8String(ref bytes) => {
+                JsonValue::stringify_wtf8_raw(bytes, buf, opts.escape)
+            }
+            JsonValue::Array(_) | JsonValue::Object(_) => unreachable!(),
+        }
+    }
+
+    fn stringify_to_buf(&self, buf: &mut String, opts: &StringifyOptions) {
+        // open containers live on an explicit stack, so that deeply nested
+        // values cannot overflow the call stack
+        let mut stack = <Vec<(Children<JsonValue>, bool)>>::new();
+        let mut next = Some(self);
+
+        loop {
+            if let Some(val) = next.take() {
+                match val.children() {
+                    Some(children) => {
+                        buf.push(children.open());
+                        stack.push((children, true));
+                    }
+                    None => val.stringify_leaf(buf, opts),
+                }
+            }
+
+            let (children, first) = match stack.last_mut() {
+                Some(top) => top,
+                None => return,
+            };
+            match children.next() {
+                Some((key, val)) => {
+                    if !*first {
                         buf.push(',');
-                        val.stringify_to_buf(buf);
                     }
+                    *first = false;
+                    if let Some(k) = key {
+                        JsonValue::stringify_string_raw(k, buf, opts.escape);
+                        buf.push(':');
+                    }
+                    next = Some(val);
+                }
+                None => {
+                    buf.push(children.close());
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    fn is_container(&self) -> bool {
+        matches!(self, JsonValue::Array(_) | JsonValue::Object(_))
+    }
+
+    /// Empties a container, moving the children that are containers onto
+    /// `stack` and dropping the others.
+    fn drain_containers(&mut self, stack: &mut Vec<Self>) {
+        match self {
+            JsonValue::Array(arr) => stack.extend(arr.drain(..).filter(JsonValue::is_container)),
+            JsonValue::Object(map) => {
+                let values = map.drain(..).map(|(_, v)| v);
+                stack.extend(values.filter(JsonValue::is_container));
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A tree of JSON values, which `Debug` and `PartialEq` walk without
+/// recursing.
+pub(crate) trait Node: Sized {
+    type Key: Deref<Target = str> + Hash + Eq;
+
+    fn as_array(&self) -> Option<&[Self]>;
+
+    fn as_object(&self) -> Option<&IndexMap<Self::Key, Self>>;
+
+    /// The variant name and contents of a value that is not a container,
+    /// with no contents for null.
+    fn leaf(&self) -> (&'static str, Option<&dyn fmt::Debug>);
+
+    /// Compares two values that are not containers.
+    fn leaf_eq(&self, other: &Self) -> bool;
+
+    fn children(&self) -> Option<Children<'_, Self>> {
+        match self.as_array() {
+            Some(arr) => Some(Children::Array(arr.iter())),
+            None => self.as_object().map(|map| Children::Object(map.iter())),
+        }
+    }
+}
+
+impl Node for JsonValue {
+    type Key = String;
+
+    fn as_array(&self) -> Option<&[JsonValue]> {
+        self.as_slice()
+    }
+
+    fn as_object(&self) -> Option<&JsonMap> {
+        self.as_map()
+    }
+
+    fn leaf(&self) -> (&'static str, Option<&dyn fmt::Debug>) {
+        match self {
+            JsonValue::Null => ("Null", None),
+            JsonValue::Boolean(b) => ("Boolean", Some(b)),
+            JsonValue::Number(n) => ("Number", Some(n)),
+            JsonValue::String(s) => ("String", Some(s)),
+            JsonValue::Wtf8String(bytes) => ("Wtf8String", Some(bytes)),
+            JsonValue::Array(_) | JsonValue::Object(_) => unreachable!(),
+        }
+    }
+
+    fn leaf_eq(&self, other: &JsonValue) -> bool {
+        match (self, other) {
+            (JsonValue::Null, JsonValue::Null) => true,
+            (JsonValue::Boolean(a), JsonValue::Boolean(b)) => a == b,
+            (JsonValue::Number(a), JsonValue::Number(b)) => a == b,
+            (JsonValue::String(a), JsonValue::String(b)) => a == b,
+            (JsonValue::Wtf8String(a), JsonValue::Wtf8String(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// The members of an array or object, keyed by name for objects.
+pub(crate) enum Children<'a, V: Node> {
+    Array(std::slice::Iter<'a, V>),
+    Object(indexmap::map::Iter<'a, V::Key, V>),
+}
+
+impl<V: Node> Children<'_, V> {
+    fn name(&self) -> &'static str {
+        match self {
+            Children::Array(_) => "Array",
+            Children::Object(_) => "Object",
+        }
+    }
+
+    fn open(&self) -> char {
+        match self {
+            Children::Array(_) => '[',
+            Children::Object(_) => '{',
+        }
+    }
+
+    fn close(&self) -> char {
+        match self {
+            Children::Array(_) => ']',
+            Children::Object(_) => '}',
+        }
+    }
+}
+
+impl<'a, V: Node> Iterator for Children<'a, V> {
+    type Item = (Option<&'a str>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Children::Array(iter) => iter.next().map(|v| (None, v)),
+            Children::Object(iter) => iter.next().map(|(k, v)| (Some(&**k), v)),
+        }
+    }
+}
+
+impl Drop for JsonValue {
+    fn drop(&mut self) {
+        // Move nested containers onto a heap-allocated stack so that each
+        // one is dropped with no containers left in it, instead of
+        // recursing. Other values are dropped where they are.
+        if !self.is_container() {
+            return;
+        }
+        let mut stack = Vec::new();
+        self.drain_containers(&mut stack);
+        while let Some(mut val) = stack.pop() {
+            val.drain_containers(&mut stack);
+        }
+    }
+}
+
+impl PartialEq for JsonValue {
+    fn eq(&self, other: &JsonValue) -> bool {
+        node_eq(self, other)
+    }
+}
+
+/// Compares like `#[derive(PartialEq)]` would, without recursing.
+pub(crate) fn node_eq<V: Node>(a: &V, b: &V) -> bool {
+    let mut stack = vec![(a, b)];
+    while let Some((a, b)) = stack.pop() {
+        if let (Some(x), Some(y)) = (a.as_array(), b.as_array()) {
+            if x.len() != y.len() {
+                return false;
+            }
+            stack.extend(x.iter().zip(y));
+        } else if let (Some(x), Some(y)) = (a.as_object(), b.as_object()) {
+            if x.len() != y.len() {
+                return false;
+            }
+            for (k, v) in x {
+                match y.get(k) {
+                    Some(w) => stack.push((v, w)),
+                    None => return false,
+                }
+            }
+        } else if a.children().is_some() || b.children().is_some() || !a.leaf_eq(b) {
+            return false;
+        }
+    }
+    true
+}
+
+fn write_indent(f: &mut fmt::Formatter, level: usize) -> fmt::Result {
+    for _ in 0..level {
+        f.write_str("    ")?;
+    }
+    Ok(())
+}
+
+fn fmt_leaf<V: Node>(val: &V, f: &mut fmt::Formatter, level: usize) -> fmt::Result {
+    let (name, inner) = match val.leaf() {
+        (name, None) => return f.write_str(name),
+        (name, Some(inner)) => (name, inner),
+    };
+    if !f.alternate() {
+        return write!(f, "{}({:?})", name, inner);
+    }
+
+    writeln!(f, "{}(", name)?;
+    write_indent(f, level + 1)?;
+    let s = format!("{:#?}", inner);
+    let mut lines = s.split('\n');
+    f.write_str(lines.next().unwrap_or_default())?;
+    for line in lines {
+        f.write_str("\n")?;
+        write_indent(f, level + 1)?;
+        f.write_str(line)?;
+    }
+    f.write_str(",\n")?;
+    write_indent(f, level)?;
+    f.write_str(")")
+}
+
+impl fmt::Debug for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_node(self, f)
+    }
+}
+
+/// Formats like `#[derive(Debug)]` would, without recursing.
+pub(crate) fn fmt_node<V: Node>(val: &V, f: &mut fmt::Formatter) -> fmt::Result {
+    let pretty = f.alternate();
+
+    // open containers with the indent level of the container itself
+    let mut stack = <Vec<(Children<V>, bool, usize)>>::new();
+    let mut next = Some((val, 0));
+
+    loop {
+        if let Some((val, level)) = next.take() {
+            match val.children() {
+                Some(children) => {
+                    write!(f, "{}(", children.name())?;
+                    if pretty {
+                        f.write_str("\n")?;
+                        write_indent(f, level + 1)?;
+                    }
+                    write!(f, "{}", children.open())?;
+                    stack.push((children, true, level));
                 }
-                buf.push(']')
+                None => fmt_leaf(val, f, level)?,
             }
-            JsonValue::Object(ref map) => {
-                buf.push('{');
-                let mut iter = map.iter();
-                for (k, v) in iter.by_ref().take(1) {
-                    JsonValue::stringify_string_raw(k, buf);
-                    buf.push(':');
-                    v.stringify_to_buf(buf);
+        }
+
+        let (children, first, level) = match stack.last_mut() {
+            Some(top) => top,
+            None => return Ok(()),
+        };
+        match children.next() {
+            Some((key, val)) => {
+                if pretty {
+                    if !*first {
+                        f.write_str(",")?;
+                    }
+                    f.write_str("\n")?;
+                    write_indent(f, *level + 2)?;
+                } else if !*first {
+                    f.write_str(", ")?;
                 }
-                for (k, v) in iter {
-                    buf.push(',');
-                    JsonValue::stringify_string_raw(k, buf);
-                    buf.push(':');
-                    v.stringify_to_buf(buf);
+                *first = false;
+                if let Some(k) = key {
+                    write!(f, "{:?}: ", k)?;
                 }
-                buf.push('}')
+                next = Some((val, *level + 2));
+            }
+            None => {
+                if pretty {
+                    if !*first {
+                        f.write_str(",\n")?;
+                        write_indent(f, *level + 1)?;
+                    }
+                    writeln!(f, "{},", children.close())?;
+                    write_indent(f, *level)?;
+                    f.write_str(")")?;
+                } else {
+                    write!(f, "{})", children.close())?;
+                }
+                stack.pop();
             }
         }
     }
@@ -101,17 +431,79 @@ pub fn is_unescaped_char(ch: char) -> bool {
     (0x20..=0x21).contains(&n) || (0x23..=0x5B).contains(&n) || (0x5D..=0x10_FFFF).contains(&n)
 }
 
+/// Like `is_unescaped_char`, for a byte of UTF-8 encoded text.
 #[inline(always)]
-pub fn is_whitespace(ch: char) -> bool {
-    if let ' ' | '\t' | '\n' | '\r' = ch {
-        true
-    } else {
-        false
+pub fn is_unescaped_byte(b: u8) -> bool {
+    b >= 0x20 && b != b'"' && b != b'\\'
+}
+
+/// The length of the run of bytes at the start of `bytes` for which
+/// `is_unescaped_byte` holds, looking at eight bytes at a time.
+pub fn unescaped_len(bytes: &[u8]) -> usize {
+    use std::convert::TryInto;
+
+    const ONES: u64 = 0x0101_0101_0101_0101;
+    const HIGH: u64 = 0x8080_8080_8080_8080;
+
+    let mut len = 0;
+    for word in bytes.chunks_exact(8) {
+        let w = u64::from_le_bytes(word.try_into().unwrap());
+        let quote = w ^ (ONES * b'"' as u64);
+        let backslash = w ^ (ONES * b'\\' as u64);
+        // the high bit of a byte is set where it is below 0x20 or zero
+        // after the xor; bytes after the first match may be flagged too
+        let found = ((w.wrapping_sub(ONES * 0x20) & !w)
+            | (quote.wrapping_sub(ONES) & !quote)
+            | (backslash.wrapping_sub(ONES) & !backslash))
+            & HIGH;
+        if found != 0 {
+            return len + found.trailing_zeros() as usize / 8;
+        }
+        len += 8;
     }
+    len + bytes[len..]
+        .iter()
+        .take_while(|&&b| is_unescaped_byte(b))
+        .count()
+}
+
+#[inline(always)]
+pub fn is_whitespace(b: u8) -> bool {
+    matches!(b, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+/// Whether `ch` is whitespace in JSON5: a JSON whitespace char, a line or
+/// paragraph separator, a byte order mark, a vertical tab, a form feed or
+/// any other Unicode space separator.
+pub fn is_json5_whitespace(ch: char) -> bool {
+    matches!(
+        ch,
+        ' ' | '\t' | '\n' | '\r' | '\u{b}' | '\u{c}' | '\u{a0}' | '\u{1680}' | '\u{2000}'
+            ..='\u{200a}'
+                | '\u{2028}'
+                | '\u{2029}'
+                | '\u{202f}'
+                | '\u{205f}'
+                | '\u{3000}'
+                | '\u{feff}'
+    )
+}
+
+/// Whether `ch` may start an unquoted JSON5 object key: `$`, `_`, or a
+/// char with the Unicode XID_Start property.
+pub fn is_identifier_start(ch: char) -> bool {
+    unicode_ident::is_xid_start(ch) || matches!(ch, '$' | '_')
+}
+
+/// Whether `ch` may occur in an unquoted JSON5 object key after its first
+/// char: `$`, ZWNJ, ZWJ, or a char with the Unicode XID_Continue property,
+/// which include those that may start it.
+pub fn is_identifier_part(ch: char) -> bool {
+    unicode_ident::is_xid_continue(ch) || matches!(ch, '$' | '\u{200c}' | '\u{200d}')
 }
 
 impl JsonValue {
-    pub fn as_num(&self) -> Option<&f64> {
+    pub fn as_num(&self) -> Option<&Number> {
         if let JsonValue::Number(ref n) = self {
             Some(n)
         } else {
@@ -127,7 +519,7 @@ impl JsonValue {
         }
     }
 
-    pub fn as_map(&self) -> Option<&HashMap<String, JsonValue>> {
+    pub fn as_map(&self) -> Option<&JsonMap> {
         if let JsonValue::Object(ref map) = self {
             Some(map)
         } else {
@@ -135,9 +527,50 @@ impl JsonValue {
         }
     }
 
+    /// Takes the value out, leaving `Null` in its place.
+    pub fn take(&mut self) -> JsonValue {
+        std::mem::replace(self, JsonValue::Null)
+    }
+
+    /// Returns the elements if the value is an array.
+    pub fn into_array(mut self) -> Option<Vec<JsonValue>> {
+        if let JsonValue::Array(ref mut arr) = self {
+            Some(std::mem::take(arr))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the members if the value is an object.
+    pub fn into_object(mut self) -> Option<JsonMap> {
+        if let JsonValue::Object(ref mut map) = self {
+            Some(std::mem::take(map))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the string if the value is a string without lone
+    /// surrogates.
+    pub fn into_string(mut self) -> Option<String> {
+        if let JsonValue::String(ref mut s) = self {
+            Some(std::mem::take(s))
+        } else {
+            None
+        }
+    }
+
+    /// Writes the value as compact JSON text.
+    ///
+    /// Non-finite floats, such as those parsed from JSON5 `Infinity` and
+    /// `NaN`, are written as `null`.
     pub fn stringify(&self) -> String {
+        self.stringify_with(&StringifyOptions::default())
+    }
+
+    pub fn stringify_with(&self, opts: &StringifyOptions) -> String {
         let mut buf = String::new();
-        self.stringify_to_buf(&mut buf);
+        self.stringify_to_buf(&mut buf, opts);
         buf
     }
 }
@@ -166,7 +599,7 @@ impl IndexMut<usize> for JsonValue {
 
 impl Index<&str> for JsonValue {
     type Output = JsonValue;
-    fn index<'a>(&self, index: &'a str) -> &JsonValue {
+    fn index(&self, index: &str) -> &JsonValue {
         if let JsonValue::Object(ref map) = self {
             &map[index]
         } else {
@@ -176,7 +609,7 @@ impl Index<&str> for JsonValue {
 }
 
 impl IndexMut<&str> for JsonValue {
-    fn index_mut<'a>(&mut self, index: &'a str) -> &mut JsonValue {
+    fn index_mut(&mut self, index: &str) -> &mut JsonValue {
         if let JsonValue::Object(ref mut map) = self {
             map.get_mut(index).expect("key not found")
         } else {
@@ -185,8 +618,8 @@ impl IndexMut<&str> for JsonValue {
     }
 }
 
-impl ToString for JsonValue {
-    fn to_string(&self) -> String {
-        self.stringify()
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.stringify())
     }
 }
@@ -7,19 +7,24 @@ enum Token {
     D19,
     Pt,
     Exp,
-    EOF,
+    /// `x` or `X`, only a token in JSON5.
+    X,
+    /// A hex digit other than `e` or `E`, only a token in JSON5.
+    Hx,
+    Eof,
 }
 
 impl Token {
-    #[inline(always)]
-    fn from_char(ch: char) -> Result<Self, ()> {
-        Ok(match ch {
-            '-' => MS,
-            '+' => PS,
-            '0' => D0,
-            '1' | '2' | '3' | '4' | '5' | '6' | '7' | '8' | '9' => D19,
-            '.' => Pt,
-            'e' | 'E' => Exp,
+    const fn from_byte(b: u8) -> Result<Self, ()> {
+        Ok(match b {
+            b'-' => MS,
+            b'+' => PS,
+            b'0' => D0,
+            b'1'..=b'9' => D19,
+            b'.' => Pt,
+            b'e' | b'E' => Exp,
+            b'x' | b'X' => X,
+            b'a'..=b'd' | b'f' | b'A'..=b'D' | b'F' => Hx,
             _ => return Err(()),
         })
     }
@@ -27,15 +32,33 @@ impl Token {
 
 use Token::*;
 
+/// The token of each byte, looked up instead of matched while scanning.
+const fn token_table(json5: bool) -> [Option<Token>; 256] {
+    let mut tokens = [None; 256];
+    let mut b = 0;
+    while b < 256 {
+        tokens[b] = match Token::from_byte(b as u8) {
+            Ok(X) | Ok(Hx) if !json5 => None,
+            Ok(tk) => Some(tk),
+            Err(_) => None,
+        };
+        b += 1;
+    }
+    tokens
+}
+
+static TOKENS: [Option<Token>; 256] = token_table(false);
+static JSON5_TOKENS: [Option<Token>; 256] = token_table(true);
+
 type State = i8;
-type StateTable = [[State; 7]; 10];
+type StateTable = [[State; 9]; 13];
 
 const START: State = 0;
-const END: State = 11;
+const END: State = 13;
 
 macro_rules! state_table {
     {$($state:expr => [$($token:expr => $target:expr $(,)?)+]$(,)?)+} => {{
-        let mut __table : StateTable = [[-1;7];10];
+        let mut __table : StateTable = [[-1;9];13];
         $(
             let __row = &mut __table[$state as usize];
             $(
@@ -51,48 +74,235 @@ fn init_table() -> StateTable {
     state_table! {
         START => [MS => 1, D0 => 2, D19 => 3],
         1 => [D0 => 2, D19 => 3],
-        2 => [Pt => 5, Exp => 7, EOF => END],
-        3 => [D0 => 4, D19 => 4, Pt => 5, Exp => 7, EOF => END],
-        4 => [D0 => 4, D19 => 4, Pt => 5, Exp => 7, EOF => END],
+        2 => [Pt => 5, Exp => 7, Eof => END],
+        3 => [D0 => 4, D19 => 4, Pt => 5, Exp => 7, Eof => END],
+        4 => [D0 => 4, D19 => 4, Pt => 5, Exp => 7, Eof => END],
         5 => [D0 => 6, D19 => 6],
-        6 => [D0 => 6, D19 => 6, Exp => 7, EOF => END],
+        6 => [D0 => 6, D19 => 6, Exp => 7, Eof => END],
         7 => [D0 => 9, D19 => 9, MS => 8, PS => 8],
         8 => [D0 => 9, D19 => 9],
-        9 => [D0 => 9, D19 => 9, EOF => END],
+        9 => [D0 => 9, D19 => 9, Eof => END],
+    }
+}
+
+/// The JSON grammar extended with a `+` sign, leading and trailing decimal
+/// points and hex integers.
+fn init_json5_table() -> StateTable {
+    state_table! {
+        START => [MS => 1, PS => 1, D0 => 2, D19 => 3, Pt => 10],
+        1 => [D0 => 2, D19 => 3, Pt => 10],
+        2 => [Pt => 5, Exp => 7, X => 11, Eof => END],
+        3 => [D0 => 4, D19 => 4, Pt => 5, Exp => 7, Eof => END],
+        4 => [D0 => 4, D19 => 4, Pt => 5, Exp => 7, Eof => END],
+        5 => [D0 => 6, D19 => 6, Exp => 7, Eof => END],
+        6 => [D0 => 6, D19 => 6, Exp => 7, Eof => END],
+        7 => [D0 => 9, D19 => 9, MS => 8, PS => 8],
+        8 => [D0 => 9, D19 => 9],
+        9 => [D0 => 9, D19 => 9, Eof => END],
+        10 => [D0 => 6, D19 => 6],
+        11 => [D0 => 12, D19 => 12, Exp => 12, Hx => 12],
+        12 => [D0 => 12, D19 => 12, Exp => 12, Hx => 12, Eof => END],
     }
 }
 
 lazy_static! {
     static ref TABLE: StateTable = init_table();
+    static ref JSON5_TABLE: StateTable = init_json5_table();
 }
 
-pub fn validate_number(s: &str) -> bool {
-    let mut chars = s.chars().peekable();
-    let mut state: State = START;
+/// The parts of a number literal, gathered while validating it.
+///
+/// When `truncated` is false the literal is exactly
+/// `mantissa * 10^exp10`, negated if `negative`.
+#[derive(Default)]
+pub struct NumberScan {
+    /// Length in bytes of the run of number characters scanned, which is
+    /// the literal itself if `valid`.
+    pub len: usize,
+    pub valid: bool,
+    pub negative: bool,
+    /// Has neither a fraction nor an exponent.
+    pub is_integer: bool,
+    pub mantissa: u64,
+    pub exp10: i64,
+    /// Digits that do not fit in `mantissa` were dropped.
+    pub truncated: bool,
+    /// A JSON5 hex integer, which is exactly `mantissa` unless `truncated`.
+    pub hex: bool,
+}
 
-    loop {
-        let row = unsafe { TABLE.get_unchecked(state as usize) };
+/// Exponents beyond this make every nonzero literal overflow or underflow,
+/// so larger ones are clamped instead of overflowing `i64`.
+const MAX_EXP: i64 = 1_000_000;
 
-        let tk = match chars.peek() {
-            None => Token::EOF,
-            Some(&ch) => match Token::from_char(ch) {
-                Err(_) => return false,
-                Ok(tk) => tk,
-            },
+/// Powers of ten that are exact in an `f64`.
+const POW10: [f64; 23] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11, 1e12, 1e13, 1e14, 1e15, 1e16,
+    1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+];
+
+impl NumberScan {
+    /// The exactly rounded value, if it can be computed cheaply with
+    /// Clinger's fast path: a mantissa of at most 53 bits scaled by an exact
+    /// power of ten takes a single correctly rounded float operation.
+    pub fn fast_f64(&self) -> Option<f64> {
+        const MAX_MANTISSA: u64 = 1 << 53;
+        if self.truncated || self.hex || self.mantissa > MAX_MANTISSA {
+            return None;
+        }
+        let m = self.mantissa as f64;
+        let num = match self.exp10 {
+            0 => m,
+            1..=22 => m * POW10[self.exp10 as usize],
+            -22..=-1 => m / POW10[-self.exp10 as usize],
+            23..=37 => {
+                // move the excess into the mantissa while it stays exact
+                let scaled = self
+                    .mantissa
+                    .checked_mul(10u64.pow(self.exp10 as u32 - 22))?;
+                if scaled > MAX_MANTISSA {
+                    return None;
+                }
+                scaled as f64 * POW10[22]
+            }
+            _ => return None,
         };
+        Some(if self.negative { -num } else { num })
+    }
+
+    #[inline(always)]
+    fn push_digit(&mut self, b: u8, fraction: bool) {
+        let digit = u64::from(b - b'0');
+        // below this, another digit cannot overflow
+        if self.mantissa < 1_000_000_000_000_000_000 && !self.truncated {
+            self.mantissa = self.mantissa * 10 + digit;
+            if fraction {
+                self.exp10 -= 1;
+            }
+            return;
+        }
+        match self
+            .mantissa
+            .checked_mul(10)
+            .and_then(|m| m.checked_add(digit))
+        {
+            Some(m) if !self.truncated => {
+                self.mantissa = m;
+                if fraction {
+                    self.exp10 -= 1;
+                }
+            }
+            _ => {
+                self.truncated = true;
+                if !fraction {
+                    self.exp10 += 1;
+                }
+            }
+        }
+    }
+
+    fn push_hex_digit(&mut self, b: u8) {
+        let digit = u64::from(char::from(b).to_digit(16).unwrap_or(0));
+        match self
+            .mantissa
+            .checked_mul(16)
+            .and_then(|m| m.checked_add(digit))
+        {
+            Some(m) if !self.truncated => self.mantissa = m,
+            _ => self.truncated = true,
+        }
+    }
+}
+
+/// Scans the number literal at the start of `bytes` in a single pass,
+/// validating it with the DFA while accumulating its value.
+///
+/// The literal ends at the first byte that cannot occur in a number.
+pub fn scan_number(bytes: &[u8]) -> NumberScan {
+    scan(bytes, &TABLE, false)
+}
 
+/// Scans a JSON5 number literal like `scan_number`, leaving `Infinity`
+/// and `NaN` to the caller.
+pub fn scan_json5_number(bytes: &[u8]) -> NumberScan {
+    scan(bytes, &JSON5_TABLE, true)
+}
+
+fn scan(bytes: &[u8], table: &StateTable, json5: bool) -> NumberScan {
+    let tokens = if json5 { &JSON5_TOKENS } else { &TOKENS };
+    let token = |b: u8| tokens[usize::from(b)].ok_or(());
+    let mut scan = NumberScan {
+        len: 0,
+        valid: false,
+        negative: false,
+        is_integer: true,
+        mantissa: 0,
+        exp10: 0,
+        truncated: false,
+        hex: false,
+    };
+    let mut state: State = START;
+    let mut exp: i64 = 0;
+    let mut exp_negative = false;
+
+    while let Some(&b) = bytes.get(scan.len) {
+        let tk = match token(b) {
+            Ok(tk) => tk,
+            Err(_) => break,
+        };
+        let row = unsafe { table.get_unchecked(state as usize) };
         let &target = unsafe { row.get_unchecked(tk as usize) };
-        if target != -1 {
-            chars.next();
-            state = target;
-        } else {
-            return false;
+        match target {
+            -1 => {
+                // report the whole run of number characters as invalid
+                scan.len += bytes[scan.len..]
+                    .iter()
+                    .take_while(|&&b| token(b).is_ok())
+                    .count();
+                return scan;
+            }
+            1 => scan.negative = b == b'-',
+            2..=4 => scan.push_digit(b, false),
+            5 | 7 | 10 => scan.is_integer = false,
+            6 => scan.push_digit(b, true),
+            8 => exp_negative = b == b'-',
+            9 => exp = (exp * 10 + i64::from(b - b'0')).min(MAX_EXP),
+            11 => scan.hex = true,
+            12 => scan.push_hex_digit(b),
+            _ => {}
         }
+        state = target;
+        scan.len += 1;
 
-        if state == END {
-            return chars.peek().is_none();
+        // the digit states loop on digits, so the rest of a run of digits
+        // is taken without going through the table
+        if matches!(state, 3 | 4 | 6 | 9) {
+            let digits = bytes[scan.len..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit())
+                .count();
+            for &b in &bytes[scan.len..scan.len + digits] {
+                match state {
+                    9 => exp = (exp * 10 + i64::from(b - b'0')).min(MAX_EXP),
+                    _ => scan.push_digit(b, state == 6),
+                }
+            }
+            if state == 3 && digits > 0 {
+                state = 4;
+            }
+            scan.len += digits;
         }
     }
+
+    let row = unsafe { table.get_unchecked(state as usize) };
+    scan.valid = row[Eof as usize] == END;
+    scan.exp10 += if exp_negative { -exp } else { exp };
+    scan
+}
+
+pub fn validate_number(s: &str) -> bool {
+    let scan = scan_number(s.as_bytes());
+    scan.valid && scan.len == s.len()
 }
 
 #[cfg(test)]
@@ -126,4 +336,97 @@ fn test_validate_number() {
     assert!(!validate_number("inf"));
     assert!(!validate_number("NAN"));
     assert!(!validate_number("nan"));
+    assert!(!validate_number("1 "));
+    assert!(!validate_number("1\u{e9}"));
+}
+
+#[cfg(test)]
+#[test]
+fn test_scan_number() {
+    let parts = |s: &str| {
+        let scan = scan_number(s.as_bytes());
+        (
+            scan.len,
+            scan.negative,
+            scan.mantissa,
+            scan.exp10,
+            scan.truncated,
+        )
+    };
+
+    assert_eq!(parts("0,"), (1, false, 0, 0, false));
+    assert_eq!(parts("-12.50e3]"), (8, true, 1250, 1, false));
+    assert_eq!(parts("0.001"), (5, false, 1, -3, false));
+    assert_eq!(parts("1E-7"), (4, false, 1, -7, false));
+    assert_eq!(
+        parts("18446744073709551615"),
+        (20, false, u64::MAX, 0, false)
+    );
+    assert_eq!(
+        parts("18446744073709551616"),
+        (20, false, 1_844_674_407_370_955_161, 1, true)
+    );
+    assert_eq!(parts("1e99999999999"), (13, false, 1, MAX_EXP, false));
+
+    let scan = scan_number(b"1.2.3 ");
+    assert_eq!((scan.len, scan.valid), (5, false));
+
+    let fast = |s: &str| scan_number(s.as_bytes()).fast_f64();
+    assert_eq!(fast("1.5"), Some(1.5));
+    assert_eq!(fast("-12.75"), Some(-12.75));
+    assert_eq!(fast("1e22"), Some(1e22));
+    assert_eq!(fast("123e30"), Some(123e30));
+    assert_eq!(fast("9007199254740993"), None);
+    assert_eq!(fast("1e-23"), None);
+    assert_eq!(fast("1e400"), None);
+
+    // the fast path must agree with the exact parser
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    for _ in 0..100_000 {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        let digits = (seed >> 11) % 10u64.pow((seed % 17) as u32 + 1);
+        let exp = (seed >> 3) % 80;
+        let s = format!("{}e{}", digits, exp as i64 - 40);
+        if let Some(num) = fast(&s) {
+            assert_eq!(num, s.parse::<f64>().unwrap(), "{}", s);
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_scan_json5_number() {
+    let parts = |s: &str| {
+        let scan = scan_json5_number(s.as_bytes());
+        (
+            scan.valid,
+            scan.len,
+            scan.negative,
+            scan.is_integer,
+            scan.mantissa,
+            scan.exp10,
+        )
+    };
+
+    assert_eq!(parts("+1"), (true, 2, false, true, 1, 0));
+    assert_eq!(parts(".5,"), (true, 2, false, false, 5, -1));
+    assert_eq!(parts("-5."), (true, 3, true, false, 5, 0));
+    assert_eq!(parts("5.e2"), (true, 4, false, false, 5, 2));
+    assert_eq!(parts("0xFf]"), (true, 4, false, true, 255, 0));
+    assert_eq!(parts("-0X1e"), (true, 5, true, true, 30, 0));
+    assert!(scan_json5_number(b"0x1234567890abcdef0").truncated);
+    assert!(scan_json5_number(b"0x10").hex);
+
+    for s in &[".", "+.", ".e1", "0x", "1x2", "01", "0x1.5", "++1", "1.5f"] {
+        let scan = scan_json5_number(s.as_bytes());
+        assert!(!scan.valid || scan.len < s.len(), "{}", s);
+    }
+    assert!(!scan_json5_number(b"1.5f").valid);
+
+    // hex digits are not number chars in JSON
+    let scan = scan_number(b"0x1");
+    assert_eq!((scan.valid, scan.len), (true, 1));
+    assert!(!validate_number("+1"));
 }
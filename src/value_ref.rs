@@ -0,0 +1,223 @@
+use crate::number::Number;
+use crate::types::{fmt_node, node_eq, JsonMap, JsonValue, Node};
+
+use indexmap::IndexMap;
+use std::borrow::Cow;
+use std::fmt;
+
+/// The members of a `JsonValueRef` object, in source order.
+pub type JsonMapRef<'a> = IndexMap<Cow<'a, str>, JsonValueRef<'a>>;
+
+/// A JSON value whose strings and object keys borrow from the source text
+/// unless they contain escapes, as parsed by `parse_borrowed`.
+pub enum JsonValueRef<'a> {
+    Null,
+    Boolean(bool),
+    Number(Number),
+    String(Cow<'a, str>),
+    /// A string holding lone surrogates, encoded as WTF-8.
+    ///
+    /// Only produced when parsing with `SurrogatePolicy::Preserve`. Bytes
+    /// that are not valid WTF-8 are stringified as U+FFFD once converted,
+    /// like those of `JsonValue::Wtf8String`.
+    Wtf8String(Vec<u8>),
+    Array(Vec<JsonValueRef<'a>>),
+    Object(JsonMapRef<'a>),
+}
+
+/// A container being converted by `JsonValueRef::into_owned`, with the
+/// children converted so far and those left.
+enum Owning<'a> {
+    Array(Vec<JsonValue>, std::vec::IntoIter<JsonValueRef<'a>>),
+    Object(
+        JsonMap,
+        String,
+        indexmap::map::IntoIter<Cow<'a, str>, JsonValueRef<'a>>,
+    ),
+}
+
+impl<'a> JsonValueRef<'a> {
+    pub fn as_str(&self) -> Option<&str> {
+        if let JsonValueRef::String(ref s) = self {
+            Some(s)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_num(&self) -> Option<&Number> {
+        if let JsonValueRef::Number(ref n) = self {
+            Some(n)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_slice(&self) -> Option<&[JsonValueRef<'a>]> {
+        if let JsonValueRef::Array(ref arr) = self {
+            Some(arr.as_slice())
+        } else {
+            None
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&JsonMapRef<'a>> {
+        if let JsonValueRef::Object(ref map) = self {
+            Some(map)
+        } else {
+            None
+        }
+    }
+
+    /// Converts into a `JsonValue`, copying the strings and keys that are
+    /// borrowed and moving the others.
+    pub fn into_owned(self) -> JsonValue {
+        let mut stack = <Vec<Owning<'a>>>::new();
+        let mut cur = self;
+        loop {
+            let mut val = match cur {
+                JsonValueRef::Array(ref mut arr) => {
+                    let arr = std::mem::take(arr);
+                    stack.push(Owning::Array(
+                        Vec::with_capacity(arr.len()),
+                        arr.into_iter(),
+                    ));
+                    None
+                }
+                JsonValueRef::Object(ref mut map) => {
+                    let map = std::mem::take(map);
+                    let owned = JsonMap::with_capacity(map.len());
+                    stack.push(Owning::Object(owned, String::new(), map.into_iter()));
+                    None
+                }
+                ref mut leaf => Some(leaf.take_leaf()),
+            };
+
+            // finish the containers whose last child this was, up to one
+            // with children left
+            loop {
+                match stack.last_mut() {
+                    None => return val.unwrap(),
+                    Some(Owning::Array(arr, rest)) => {
+                        arr.extend(val.take());
+                        if let Some(next) = rest.next() {
+                            cur = next;
+                            break;
+                        }
+                        val = Some(JsonValue::Array(std::mem::take(arr)));
+                    }
+                    Some(Owning::Object(map, key, rest)) => {
+                        if let Some(v) = val.take() {
+                            map.insert(std::mem::take(key), v);
+                        }
+                        if let Some((k, next)) = rest.next() {
+                            *key = k.into_owned();
+                            cur = next;
+                            break;
+                        }
+                        val = Some(JsonValue::Object(std::mem::take(map)));
+                    }
+                }
+                stack.pop();
+            }
+        }
+    }
+
+    fn take_leaf(&mut self) -> JsonValue {
+        match self {
+            JsonValueRef::Null => JsonValue::Null,
+            JsonValueRef::Boolean(b) => JsonValue::Boolean(*b),
+            JsonValueRef::Number(n) => JsonValue::Number(std::mem::replace(n, 0.into())),
+            JsonValueRef::String(s) => JsonValue::String(std::mem::take(s).into_owned()),
+            JsonValueRef::Wtf8String(bytes) => JsonValue::Wtf8String(std::mem::take(bytes)),
+            JsonValueRef::Array(_) | JsonValueRef::Object(_) => unreachable!(),
+        }
+    }
+
+    fn is_container(&self) -> bool {
+        matches!(self, JsonValueRef::Array(_) | JsonValueRef::Object(_))
+    }
+
+    /// Empties a container, moving the children that are containers onto
+    /// `stack` and dropping the others.
+    fn drain_containers(&mut self, stack: &mut Vec<Self>) {
+        match self {
+            JsonValueRef::Array(arr) => {
+                stack.extend(arr.drain(..).filter(JsonValueRef::is_container))
+            }
+            JsonValueRef::Object(map) => {
+                let values = map.drain(..).map(|(_, v)| v);
+                stack.extend(values.filter(JsonValueRef::is_container));
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<'a> From<JsonValueRef<'a>> for JsonValue {
+    fn from(val: JsonValueRef<'a>) -> Self {
+        val.into_owned()
+    }
+}
+
+impl<'a> Node for JsonValueRef<'a> {
+    type Key = Cow<'a, str>;
+
+    fn as_array(&self) -> Option<&[JsonValueRef<'a>]> {
+        self.as_slice()
+    }
+
+    fn as_object(&self) -> Option<&JsonMapRef<'a>> {
+        self.as_map()
+    }
+
+    fn leaf(&self) -> (&'static str, Option<&dyn fmt::Debug>) {
+        match self {
+            JsonValueRef::Null => ("Null", None),
+            JsonValueRef::Boolean(b) => ("Boolean", Some(b)),
+            JsonValueRef::Number(n) => ("Number", Some(n)),
+            JsonValueRef::String(s) => ("String", Some(s)),
+            JsonValueRef::Wtf8String(bytes) => ("Wtf8String", Some(bytes)),
+            JsonValueRef::Array(_) | JsonValueRef::Object(_) => unreachable!(),
+        }
+    }
+
+    fn leaf_eq(&self, other: &JsonValueRef<'a>) -> bool {
+        match (self, other) {
+            (JsonValueRef::Null, JsonValueRef::Null) => true,
+            (JsonValueRef::Boolean(a), JsonValueRef::Boolean(b)) => a == b,
+            (JsonValueRef::Number(a), JsonValueRef::Number(b)) => a == b,
+            (JsonValueRef::String(a), JsonValueRef::String(b)) => a == b,
+            (JsonValueRef::Wtf8String(a), JsonValueRef::Wtf8String(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+// like `JsonValue`, compared and formatted without recursing
+impl PartialEq for JsonValueRef<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        node_eq(self, other)
+    }
+}
+
+impl fmt::Debug for JsonValueRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_node(self, f)
+    }
+}
+
+impl Drop for JsonValueRef<'_> {
+    fn drop(&mut self) {
+        // like `JsonValue`, drop nested containers from a heap-allocated
+        // stack instead of recursing
+        if !self.is_container() {
+            return;
+        }
+        let mut stack = Vec::new();
+        self.drain_containers(&mut stack);
+        while let Some(mut val) = stack.pop() {
+            val.drain_containers(&mut stack);
+        }
+    }
+}
@@ -1,9 +1,19 @@
-use json_rs::{JsonError, JsonValue};
+#![allow(clippy::approx_constant, clippy::excessive_precision)]
+
+use json_rs::{
+    Dialect, DuplicateKeyPolicy, EscapeMode, JsonBuilder, JsonErrorKind, JsonEvent, JsonMap,
+    JsonMapRef, JsonValue, JsonValueBuilder, JsonValueRef, LossyNumberPolicy, Number, ParseMode,
+    ParseOptions, StringifyOptions, SurrogatePolicy,
+};
+use std::borrow::Cow;
 
 macro_rules! expect {
-    ($src:expr, $res:expr) => {
-        assert_eq!(json_rs::parse($src), $res);
-    };
+    ($src:expr, $res:expr) => {{
+        let res = json_rs::parse($src);
+        let iterative = ParseOptions::new().mode(ParseMode::Iterative);
+        assert_eq!(json_rs::parse_with($src, &iterative), res);
+        assert_eq!(res.map_err(|e| e.kind()), $res);
+    }};
 }
 
 macro_rules! expect_ok {
@@ -20,7 +30,7 @@ macro_rules! expect_err {
 
 macro_rules! expect_num {
     ($src:expr,$num:expr) => {
-        expect_ok!($src, JsonValue::Number($num));
+        expect_ok!($src, JsonValue::Number($num.into()));
     };
 }
 
@@ -46,40 +56,40 @@ macro_rules! expect_val {
 
 #[test]
 fn test_parse() {
-    expect_err!(" l ", JsonError::InvalidValue);
-    expect_err!(" ", JsonError::UnexpectedEnd);
-    expect_err!("", JsonError::UnexpectedEnd);
+    expect_err!(" l ", JsonErrorKind::InvalidValue);
+    expect_err!(" ", JsonErrorKind::UnexpectedEnd);
+    expect_err!("", JsonErrorKind::UnexpectedEnd);
 }
 
 #[test]
 fn test_parse_null() {
     expect_ok!("null", JsonValue::Null);
     expect_ok!(" null ", JsonValue::Null);
-    expect_err!("nul", JsonError::UnexpectedEnd);
-    expect_err!(" nulll", JsonError::InvalidValue);
-    expect_err!(" null n", JsonError::RootNotSingular);
+    expect_err!("nul", JsonErrorKind::UnexpectedEnd);
+    expect_err!(" nulll", JsonErrorKind::TrailingCharacters);
+    expect_err!(" null n", JsonErrorKind::RootNotSingular);
 }
 
 #[test]
 fn test_parse_bool() {
     expect_ok!("true", JsonValue::Boolean(true));
     expect_ok!(" true ", JsonValue::Boolean(true));
-    expect_err!(" true t", JsonError::RootNotSingular);
+    expect_err!(" true t", JsonErrorKind::RootNotSingular);
 
     expect_ok!("false", JsonValue::Boolean(false));
     expect_ok!(" false ", JsonValue::Boolean(false));
-    expect_err!(" false t", JsonError::RootNotSingular);
+    expect_err!(" false t", JsonErrorKind::RootNotSingular);
 }
 
 #[test]
 fn test_parse_num() {
-    expect_num!("0", 0.0);
-    expect_num!("0 ", 0.0);
-    expect_num!(" 0 ", 0.0);
+    expect_num!("0", 0);
+    expect_num!("0 ", 0);
+    expect_num!(" 0 ", 0);
     expect_num!("-0", 0.0);
     expect_num!("-0.0", 0.0);
-    expect_num!("1", 1.0);
-    expect_num!("-1", -1.0);
+    expect_num!("1", 1);
+    expect_num!("-1", -1);
     expect_num!("1.5", 1.5);
     expect_num!("-1.5", -1.5);
     expect_num!("3.1416", 3.1416);
@@ -95,18 +105,18 @@ fn test_parse_num() {
     expect_num!("1.234E-10", 1.234E-10);
     expect_num!("1e-10000", 0.0); /* must underflow */
 
-    expect_err!("+0+ ", JsonError::InvalidValue);
-    expect_err!("+0", JsonError::InvalidValue);
-    expect_err!("+1", JsonError::InvalidValue);
-    expect_err!(".123", JsonError::InvalidValue);
-    expect_err!("1.", JsonError::InvalidValue);
-    expect_err!("INF", JsonError::InvalidValue);
-    expect_err!("inf", JsonError::InvalidValue);
-    expect_err!("NAN", JsonError::InvalidValue);
-    expect_err!("nan", JsonError::InvalidValue);
-    expect_err!("0123", JsonError::InvalidValue);
-    expect_err!("0m", JsonError::InvalidValue);
-    expect_err!("123.", JsonError::InvalidValue);
+    expect_err!("+0+ ", JsonErrorKind::InvalidValue);
+    expect_err!("+0", JsonErrorKind::InvalidValue);
+    expect_err!("+1", JsonErrorKind::InvalidValue);
+    expect_err!(".123", JsonErrorKind::InvalidValue);
+    expect_err!("1.", JsonErrorKind::InvalidNumber);
+    expect_err!("INF", JsonErrorKind::InvalidValue);
+    expect_err!("inf", JsonErrorKind::InvalidValue);
+    expect_err!("NAN", JsonErrorKind::InvalidValue);
+    expect_err!("nan", JsonErrorKind::InvalidLiteral);
+    expect_err!("0123", JsonErrorKind::InvalidNumber);
+    expect_err!("0m", JsonErrorKind::TrailingCharacters);
+    expect_err!("123.", JsonErrorKind::InvalidNumber);
 
     /* the smallest number > 1 */
     expect_num!("1.0000000000000002", 1.0000000000000002);
@@ -123,22 +133,105 @@ fn test_parse_num() {
     expect_num!("1.7976931348623157e+308", 1.7976931348623157e+308);
     expect_num!("-1.7976931348623157e+308", -1.7976931348623157e+308);
 
-    expect_err!("1e+400", JsonError::NumberTooBig);
-    expect_err!("-1e+400", JsonError::NumberTooBig);
+    expect_err!("1e+400", JsonErrorKind::NumberTooBig);
+    expect_err!("-1e+400", JsonErrorKind::NumberTooBig);
 }
 
 #[test]
 fn test_parse_str() {
-    expect_err!(r#"""#, JsonError::UnexpectedEnd);
-    expect_err!(r#""\""#, JsonError::UnexpectedEnd);
-    expect_err!("\"\u{22}\"", JsonError::InvalidValue);
-    expect_err!(r#""\u""#, JsonError::InvalidValue);
-    expect_err!("   \"\"  \"\" ", JsonError::RootNotSingular);
+    expect_err!(r#"""#, JsonErrorKind::UnexpectedEnd);
+    expect_err!(r#""\""#, JsonErrorKind::UnexpectedEnd);
+    expect_err!("\"\u{22}\"", JsonErrorKind::TrailingCharacters);
+    expect_err!(r#""\u""#, JsonErrorKind::InvalidUnicodeEscape);
+    expect_err!("   \"\"  \"\" ", JsonErrorKind::RootNotSingular);
+
+    expect_err!(r#""\x""#, JsonErrorKind::InvalidEscape);
+    expect_err!(r#""\u12g4""#, JsonErrorKind::InvalidUnicodeEscape);
+    expect_err!("\"a\u{1}b\"", JsonErrorKind::ControlCharacterInString);
+    expect_err!("\"a\nb\"", JsonErrorKind::ControlCharacterInString);
 
     expect_str!(r#""\\""#, r#"\"#);
     expect_str!(r#""\t""#, "\t");
     expect_str!(r#""\n""#, "\n");
     expect_str!(r#""\u1234ab""#, "\u{1234}ab");
+
+    // special chars at every offset of the 8-byte words strings are scanned in
+    for i in 0..20 {
+        let pad = "\u{e9}abcdefghijklmnopqrs"
+            .chars()
+            .take(i)
+            .collect::<String>();
+        expect_str!(&format!(r#""{}\"~""#, pad), format!("{}\"~", pad));
+        expect_str!(
+            &format!(r#""{}\\{}""#, pad, pad),
+            format!("{}\\{}", pad, pad)
+        );
+        expect_err!(
+            &format!("\"{}\u{1f}\"", pad),
+            JsonErrorKind::ControlCharacterInString
+        );
+        expect_err!(&format!("\"{}", pad), JsonErrorKind::UnexpectedEnd);
+    }
+}
+
+#[test]
+fn test_parse_surrogate() {
+    expect_str!(r#""\ud83d\ude00""#, "\u{1F600}");
+    expect_str!(r#""\uD834\uDD1E""#, "\u{1D11E}");
+    expect_str!(r#""a\u0041\u00e9""#, "aA\u{e9}");
+
+    expect_err!(r#""\ud83d""#, JsonErrorKind::LoneSurrogate);
+    expect_err!(r#""\ude00\ud83d""#, JsonErrorKind::LoneSurrogate);
+    expect_err!(r#""\ud83d\u0041""#, JsonErrorKind::LoneSurrogate);
+    expect_err!(r#""\ud83d\u004""#, JsonErrorKind::InvalidUnicodeEscape);
+
+    let err = json_rs::parse(r#"["\u0041\ud83dx"]"#).unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::LoneSurrogate);
+    assert_eq!(err.column(), 9);
+
+    let replace = ParseOptions::new().lone_surrogates(SurrogatePolicy::Replace);
+    let parse = |src| json_rs::parse_with(src, &replace);
+    assert_eq!(
+        parse(r#""\ud83d\u0041""#),
+        Ok(JsonValue::String("\u{FFFD}A".to_owned()))
+    );
+    assert_eq!(
+        parse(r#""\ud83d\ud83d\ude00\ude00""#),
+        Ok(JsonValue::String("\u{FFFD}\u{1F600}\u{FFFD}".to_owned()))
+    );
+    assert_eq!(
+        parse(r#"{"\udc00":1}"#).unwrap()["\u{FFFD}"],
+        JsonValue::Number(1.into())
+    );
+
+    let preserve = ParseOptions::new().lone_surrogates(SurrogatePolicy::Preserve);
+    let parse = |src: &str| json_rs::parse_with(src, &preserve);
+    assert_eq!(
+        parse(r#""\ud83d\ude00""#),
+        Ok(JsonValue::String("\u{1F600}".to_owned()))
+    );
+    assert_eq!(
+        parse(r#""a\ud83d""#),
+        Ok(JsonValue::Wtf8String(vec![b'a', 0xED, 0xA0, 0xBD]))
+    );
+    assert_eq!(parse(r#""a\ud800b""#).unwrap().stringify(), r#""a\ud800b""#);
+    let val = parse(r#""\udfff\u00e9\ud83d\ude00\udbff""#).unwrap();
+    assert_eq!(val.stringify(), "\"\\udfff\u{e9}\u{1F600}\\udbff\"");
+    assert_eq!(parse(&val.stringify()), Ok(val));
+    // bytes that are not WTF-8 are replaced rather than written out
+    for (bytes, expected) in &[
+        (&[b'a', 0xED][..], "\"a\u{FFFD}\""),
+        (&[0xED, 0xA0], "\"\u{FFFD}\u{FFFD}\""),
+        (&[0xFF, b'b', 0xED, 0xB0, 0x80], "\"\u{FFFD}b\\udc00\""),
+        (&[0xC3, b'"', 0xF0, 0x9F, 0x98], "\"\u{FFFD}\\\"\u{FFFD}\""),
+    ] {
+        let val = JsonValue::Wtf8String(bytes.to_vec());
+        assert_eq!(val.stringify(), *expected);
+    }
+    assert_eq!(
+        parse(r#"{"\ud800":1}"#).map_err(|e| e.kind()),
+        Err(JsonErrorKind::LoneSurrogate)
+    );
 }
 
 #[test]
@@ -151,7 +244,7 @@ fn test_parse_array() {
             JsonValue::Null,
             JsonValue::Boolean(false),
             JsonValue::Boolean(true),
-            JsonValue::Number(123.0),
+            JsonValue::Number(123.into()),
             JsonValue::String("abc".to_owned())
         ]
     );
@@ -160,9 +253,9 @@ fn test_parse_array() {
         r#"[ [ ] , [ 0 ] , [ 0 , 1 ] , [ 0 , 1 , 2 ] ]"#,
         &[
             Array(vec![]),
-            Array(vec![Number(0.)]),
-            Array(vec![Number(0.), Number(1.),]),
-            Array(vec![Number(0.), Number(1.), Number(2.),]),
+            Array(vec![Number(0.into())]),
+            Array(vec![Number(0.into()), Number(1.into()),]),
+            Array(vec![Number(0.into()), Number(1.into()), Number(2.into()),]),
         ]
     );
 
@@ -170,32 +263,1502 @@ fn test_parse_array() {
     expect_array!("[null   ,   [null]]\n", &[Null, Array(vec![Null])]);
     expect_array!("[null\t,\t[null]]\n", &[Null, Array(vec![Null])]);
 
-    expect_err!("[null     ,     [null,]]\n", JsonError::InvalidValue);
+    expect_err!("[null     ,     [null,]]\n", JsonErrorKind::TrailingComma);
 
-    expect_err!("[", JsonError::UnexpectedEnd);
+    expect_err!("[", JsonErrorKind::UnexpectedEnd);
 
-    expect_err!("[nulll]", JsonError::InvalidValue);
+    expect_err!("[nulll]", JsonErrorKind::TrailingCharacters);
+    expect_err!("[null,]", JsonErrorKind::TrailingComma);
+    expect_err!("[null,,null]", JsonErrorKind::InvalidValue);
+    expect_err!("[null null]", JsonErrorKind::ExpectedCommaOrBracket);
+    expect_err!("[null}", JsonErrorKind::ExpectedCommaOrBracket);
 }
 
 #[test]
 fn test_parse_object() {
-    expect_err!("{:1,", JsonError::InvalidValue);
-    expect_err!("{1:1,", JsonError::InvalidValue);
-    expect_err!("{true:1,", JsonError::InvalidValue);
-    expect_err!("{false:1,", JsonError::InvalidValue);
-    expect_err!("{null:1,", JsonError::InvalidValue);
-    expect_err!("{[]:1,", JsonError::InvalidValue);
-    expect_err!("{{}:1,", JsonError::InvalidValue);
-    expect_err!(r#"{"a":1]"#, JsonError::InvalidValue);
-    expect_err!(r#"{"a":1 "b""#, JsonError::InvalidValue);
-
-    expect_err!(r#"{"a"}"#, JsonError::MissingColon);
-    expect_err!(r#"{"a","b"}"#, JsonError::MissingColon);
-
-    expect_err!(r#"{"a":1,"#, JsonError::UnexpectedEnd);
-    expect_err!(r#"{"a":{}"#, JsonError::UnexpectedEnd);
-    expect_err!(r#"{"a":1"#, JsonError::UnexpectedEnd);
+    expect_err!("{:1,", JsonErrorKind::ExpectedObjectKey);
+    expect_err!("{1:1,", JsonErrorKind::ExpectedObjectKey);
+    expect_err!("{true:1,", JsonErrorKind::ExpectedObjectKey);
+    expect_err!("{false:1,", JsonErrorKind::ExpectedObjectKey);
+    expect_err!("{null:1,", JsonErrorKind::ExpectedObjectKey);
+    expect_err!("{[]:1,", JsonErrorKind::ExpectedObjectKey);
+    expect_err!("{{}:1,", JsonErrorKind::ExpectedObjectKey);
+    expect_err!(r#"{"a":1]"#, JsonErrorKind::ExpectedCommaOrBrace);
+    expect_err!(r#"{"a":1 "b""#, JsonErrorKind::ExpectedCommaOrBrace);
+
+    expect_err!(r#"{"a":1,}"#, JsonErrorKind::TrailingComma);
+    expect_err!(r#"{"a":1,2:3}"#, JsonErrorKind::ExpectedObjectKey);
+
+    expect_err!(r#"{"a"}"#, JsonErrorKind::MissingColon);
+    expect_err!(r#"{"a","b"}"#, JsonErrorKind::MissingColon);
+
+    expect_err!(r#"{"a":1,"#, JsonErrorKind::UnexpectedEnd);
+    expect_err!(r#"{"a":{}"#, JsonErrorKind::UnexpectedEnd);
+    expect_err!(r#"{"a":1"#, JsonErrorKind::UnexpectedEnd);
 
     expect_val!(r#"{"a":null,"b":null}"#, ["a"], JsonValue::Null);
     expect_val!(r#"{"a":{"b":null}}"#, ["a", "b"], JsonValue::Null);
 }
+
+#[test]
+fn test_error_position() {
+    let err = json_rs::parse("[1,\n  nul]").unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::InvalidLiteral);
+    assert_eq!((err.offset(), err.line(), err.column()), (9, 2, 6));
+
+    let err = json_rs::parse("{\"\u{e9}\u{e9}\":1 x").unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::ExpectedCommaOrBrace);
+    assert_eq!((err.offset(), err.line(), err.column()), (10, 1, 9));
+
+    let err = json_rs::parse("[\"\u{1F600}a\\n\u{e9}\u{1}\"]").unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::ControlCharacterInString);
+    assert_eq!((err.offset(), err.line(), err.column()), (11, 1, 8));
+
+    let err = json_rs::parse("").unwrap_err();
+    assert_eq!((err.offset(), err.line(), err.column()), (0, 1, 1));
+
+    assert_eq!(
+        err.to_string(),
+        "unexpected end of input, expected value at line 1 column 1"
+    );
+}
+
+#[test]
+fn test_error_expected() {
+    let err = json_rs::parse("[1 2]").unwrap_err();
+    assert_eq!(err.expected(), &["','", "']'"]);
+    assert_eq!(
+        err.to_string(),
+        "unexpected character after array element, expected ',' or ']' at line 1 column 4"
+    );
+
+    let err = json_rs::parse("[").unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::UnexpectedEnd);
+    assert_eq!(err.expected(), &["value", "']'"]);
+
+    let err = json_rs::parse(r#"{"a" 1}"#).unwrap_err();
+    assert_eq!(err.expected(), &["':'"]);
+
+    let err = json_rs::parse("tru").unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::UnexpectedEnd);
+    assert_eq!(err.expected(), &["true"]);
+}
+
+#[test]
+fn test_error_render() {
+    let src = "{\n  \"a\": [1 2]\n}\n";
+    let err = json_rs::parse(src).unwrap_err();
+    assert_eq!(
+        err.render(src),
+        concat!(
+            "error: unexpected character after array element, expected ',' or ']'\n",
+            " --> line 2 column 11\n",
+            "  |\n",
+            "2 |   \"a\": [1 2]\n",
+            "  |           ^\n",
+            "  = path: /a",
+        )
+    );
+    // only the end of the text, starting at a line or in the middle of one
+    assert_eq!(err.render_from(&src[2..], 2), err.render(src));
+    assert_eq!(
+        err.render_from(&src[9..], 9),
+        concat!(
+            "error: unexpected character after array element, expected ',' or ']'\n",
+            " --> line 2 column 11\n",
+            "  |\n",
+            "2 | [1 2]\n",
+            "  |    ^\n",
+            "  = path: /a",
+        )
+    );
+
+    let src = "[\r\n\tnul\r\n";
+    let err = json_rs::parse(src).unwrap_err();
+    assert_eq!(
+        err.render(src),
+        concat!(
+            "error: invalid literal, expected null\n",
+            " --> line 2 column 5\n",
+            "  |\n",
+            "2 | \tnul\n",
+            "  | \t   ^\n",
+            "  = path: /0",
+        )
+    );
+}
+
+#[test]
+fn test_error_path() {
+    macro_rules! expect_path {
+        ($src:expr, $path:expr) => {
+            assert_eq!(json_rs::parse($src).unwrap_err().path(), $path);
+        };
+    }
+
+    expect_path!("nul", "");
+    expect_path!("[1, 2 3]", "");
+    expect_path!("[1, nul]", "/1");
+    expect_path!(r#"{"a": [1, {"b": x}]}"#, "/a/1/b");
+    expect_path!(r#"{"a": 1 "b": 2}"#, "");
+    expect_path!(r#"{"a": {"b" 1}}"#, "/a/b");
+    expect_path!(r#"{"a/b": {"m~n": [0, -]}}"#, "/a~1b/m~0n/1");
+    expect_path!(
+        r#"{"services": [{}, {}, {}, {"ports": [80, 44x]}]}"#,
+        "/services/3/ports/1"
+    );
+
+    let err = json_rs::parse("[[], [tru]]").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid literal, expected true at line 1 column 10 in /1/0"
+    );
+}
+
+#[test]
+fn test_stringify_escape() {
+    let stringify = |s: &str, mode| {
+        JsonValue::String(s.to_owned()).stringify_with(&StringifyOptions::new().escape(mode))
+    };
+
+    let s = "a\"\\/\u{8}\u{c}\n\r\t\u{0}\u{1f}~";
+    assert_eq!(
+        stringify(s, EscapeMode::Minimal),
+        r#""a\"\\/\b\f\n\r\t\u0000\u001f~""#
+    );
+    assert_eq!(
+        stringify("\u{1}\u{1a}\u{1F}", EscapeMode::Minimal),
+        r#""\u0001\u001a\u001f""#
+    );
+
+    let s = "<a href='x'>&\u{e9}\u{2028}\u{2029}\u{1F600}</a>";
+    assert_eq!(stringify(s, EscapeMode::Minimal), format!("\"{}\"", s));
+    assert_eq!(
+        stringify(s, EscapeMode::AsciiOnly),
+        r#""<a href='x'>&\u00e9\u2028\u2029\ud83d\ude00</a>""#
+    );
+    assert_eq!(
+        stringify(s, EscapeMode::HtmlSafe),
+        "\"\\u003ca href=\\u0027x\\u0027\\u003e\\u0026\u{e9}\\u2028\\u2029\u{1F600}\\u003c/a\\u003e\""
+    );
+    assert_eq!(
+        stringify(s, EscapeMode::JsSafe),
+        "\"<a href='x'>&\u{e9}\\u2028\\u2029\u{1F600}</a>\""
+    );
+
+    for mode in &[
+        EscapeMode::Minimal,
+        EscapeMode::AsciiOnly,
+        EscapeMode::HtmlSafe,
+        EscapeMode::JsSafe,
+    ] {
+        let val = JsonValue::String(s.to_owned());
+        let out = json_rs::stringify_with(&val, &StringifyOptions::new().escape(*mode));
+        assert_eq!(json_rs::parse(&out), Ok(val));
+    }
+}
+
+#[test]
+fn test_max_depth() {
+    let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+
+    let depth = ParseOptions::DEFAULT_MAX_DEPTH;
+    assert!(json_rs::parse(&nested(depth)).is_ok());
+    let err = json_rs::parse(&nested(depth + 1)).unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::DepthLimitExceeded);
+    assert_eq!(err.offset(), depth);
+
+    let err = json_rs::parse(&"[".repeat(100_000)).unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::DepthLimitExceeded);
+
+    let opts = ParseOptions::new().max_depth(Some(2));
+    let parse = |src| json_rs::parse_with(src, &opts).map_err(|e| e.kind());
+    assert!(parse(r#"{"a": [1], "b": {}}"#).is_ok());
+    assert_eq!(
+        parse(r#"{"a": [{}]}"#),
+        Err(JsonErrorKind::DepthLimitExceeded)
+    );
+    assert_eq!(parse("[[[]]]"), Err(JsonErrorKind::DepthLimitExceeded));
+
+    let opts = ParseOptions::new().max_depth(Some(0));
+    assert!(json_rs::parse_with("1", &opts).is_ok());
+    assert!(json_rs::parse_with("[]", &opts).is_err());
+
+    let opts = ParseOptions::new().max_depth(None);
+    assert!(json_rs::parse_with(&nested(300), &opts).is_ok());
+}
+
+#[test]
+fn test_parse_iterative() {
+    let opts = ParseOptions::new().mode(ParseMode::Iterative);
+
+    let src = r#"{"a": [1, [], {}, {"b": [null, true, "x"]}], "c": {"d": [[[]]]}}"#;
+    assert_eq!(json_rs::parse_with(src, &opts), json_rs::parse(src));
+
+    for src in &[
+        "[[], [tru]]",
+        r#"{"a": [1, {"b": x}]}"#,
+        r#"{"a": {"b" 1}}"#,
+        r#"{"a": [{}]} x"#,
+        "[[]x]",
+        "[[1,]]",
+        r#"[{"a":1,}]"#,
+    ] {
+        assert_eq!(json_rs::parse_with(src, &opts), json_rs::parse(src));
+    }
+
+    let depth = 10_000;
+    let src = format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+    let err = json_rs::parse_with(&src, &opts).unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::DepthLimitExceeded);
+
+    let opts = opts.max_depth(None);
+    let val = json_rs::parse_with(&src, &opts).unwrap();
+    let mut inner = &val;
+    for _ in 0..depth - 1 {
+        inner = &inner[0];
+    }
+    assert_eq!(inner, &JsonValue::Array(vec![]));
+
+    let src = format!("{}1", r#"{"a":["#.repeat(100_000));
+    let err = json_rs::parse_with(&src, &opts).unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::UnexpectedEnd);
+    assert!(err.path().ends_with("/a/0/a"));
+}
+
+#[test]
+fn test_debug() {
+    let val = json_rs::parse(r#"[1, "a", {"b": [true, null]}, []]"#).unwrap();
+    assert_eq!(
+        format!("{:?}", val),
+        r#"Array([Number(1), String("a"), Object({"b": Array([Boolean(true), Null])}), Array([])])"#
+    );
+    assert_eq!(
+        format!("{:#?}", val),
+        r#"Array(
+    [
+        Number(
+            1,
+        ),
+        String(
+            "a",
+        ),
+        Object(
+            {
+                "b": Array(
+                    [
+                        Boolean(
+                            true,
+                        ),
+                        Null,
+                    ],
+                ),
+            },
+        ),
+        Array(
+            [],
+        ),
+    ],
+)"#
+    );
+}
+
+#[test]
+fn test_deep_value() {
+    let depth = 1_000_000;
+    let deep = |leaf| {
+        let mut val = leaf;
+        for i in 0..depth {
+            val = if i % 2 == 0 {
+                JsonValue::Array(vec![JsonValue::Null, val])
+            } else {
+                let mut map = JsonMap::new();
+                map.insert("k".to_owned(), val);
+                JsonValue::Object(map)
+            };
+        }
+        val
+    };
+
+    let a = deep(JsonValue::Number(1.into()));
+    assert_eq!(a, deep(JsonValue::Number(1.into())));
+    assert_ne!(a, deep(JsonValue::Number(2.into())));
+
+    let s = a.stringify();
+    assert_eq!(
+        s.len(),
+        1 + depth / 2 * ("[null,".len() + "{\"k\":".len() + 2)
+    );
+    assert!(s.starts_with(r#"{"k":[null,{"k":[null,"#));
+    assert!(s.contains(r#"{"k":[null,1]}]}"#));
+
+    let d = format!("{:?}", a);
+    assert!(d.starts_with(r#"Object({"k": Array([Null, Object({"k": "#));
+    assert!(d.contains(r#"{"k": Array([Null, Number(1)])})])})"#));
+
+    let deep_ref = |leaf| {
+        let mut val = leaf;
+        for i in 0..depth {
+            val = if i % 2 == 0 {
+                JsonValueRef::Array(vec![JsonValueRef::Null, val])
+            } else {
+                let mut map = JsonMapRef::new();
+                map.insert(Cow::Borrowed("k"), val);
+                JsonValueRef::Object(map)
+            };
+        }
+        val
+    };
+
+    let r = deep_ref(JsonValueRef::Number(1.into()));
+    assert_eq!(r, deep_ref(JsonValueRef::Number(1.into())));
+    assert_ne!(r, deep_ref(JsonValueRef::Number(2.into())));
+    assert_eq!(format!("{:?}", r), d);
+}
+
+#[test]
+fn test_into_accessors() {
+    let mut val = json_rs::parse(r#"{"a": [1, "x"], "b": "y"}"#).unwrap();
+    let mut arr = val["a"].take().into_array().unwrap();
+    assert_eq!(val["a"], JsonValue::Null);
+    assert_eq!(arr[0], JsonValue::Number(1.into()));
+    assert_eq!(arr[1].take().into_string(), Some("x".to_owned()));
+    assert_eq!(JsonValue::Null.into_array(), None);
+
+    let map = val.into_object().unwrap();
+    assert_eq!(map.keys().collect::<Vec<_>>(), ["a", "b"]);
+    assert_eq!(JsonValue::Array(vec![]).into_object(), None);
+    assert_eq!(JsonValue::Null.into_string(), None);
+}
+
+#[test]
+fn test_limits() {
+    for mode in &[ParseMode::Recursive, ParseMode::Iterative] {
+        let base = ParseOptions::new().mode(*mode);
+        let parse = |src: &str, opts: ParseOptions| json_rs::parse_with(src, &opts);
+        let kind = |src: &str, opts: ParseOptions| parse(src, opts).map_err(|e| e.kind());
+
+        let opts = base.max_input_len(Some(8));
+        assert!(parse("[1, 2,3]", opts).is_ok());
+        assert_eq!(kind("[1, 2, 3]", opts), Err(JsonErrorKind::InputTooLarge));
+
+        let opts = base.max_string_len(Some(3));
+        assert!(parse(r#"["abc", "\u00e9x"]"#, opts).is_ok());
+        assert_eq!(kind(r#""abcd""#, opts), Err(JsonErrorKind::StringTooLong));
+        assert_eq!(
+            kind(r#""\u00e9\u00e9""#, opts),
+            Err(JsonErrorKind::StringTooLong)
+        );
+        let err = parse(r#"{"a": 1, "long": 2}"#, opts).unwrap_err();
+        assert_eq!(err.kind(), JsonErrorKind::StringTooLong);
+        assert_eq!(err.column(), 10);
+
+        let opts = base.max_number_len(Some(4));
+        assert!(parse("[-1.5, 1e10]", opts).is_ok());
+        let err = parse("[1, 12345]", opts).unwrap_err();
+        assert_eq!(err.kind(), JsonErrorKind::NumberTooLong);
+        assert_eq!((err.column(), err.path()), (5, "/1"));
+        assert_eq!(
+            kind(&"1".repeat(1_000_000), opts),
+            Err(JsonErrorKind::NumberTooLong)
+        );
+        // an endless number or key is cut off at the limit
+        let endless = std::io::Read::chain(&b"[1"[..], std::io::repeat(b'1'));
+        let err = json_rs::parse_reader_with(endless, &opts).unwrap_err();
+        assert_eq!(err.kind(), JsonErrorKind::NumberTooLong);
+        let opts = base.dialect(Dialect::Json5).max_string_len(Some(3));
+        let endless = std::io::Read::chain(&b"{a"[..], std::io::repeat(b'a'));
+        let err = json_rs::parse_reader_with(endless, &opts).unwrap_err();
+        assert_eq!(err.kind(), JsonErrorKind::StringTooLong);
+
+        let opts = base.max_array_len(Some(2));
+        assert!(parse("[[1, 2], [], [3]]", opts).is_err());
+        assert!(parse("[[1, 2], []]", opts).is_ok());
+        let err = parse("[[1, 2, 3]]", opts).unwrap_err();
+        assert_eq!(err.kind(), JsonErrorKind::TooManyElements);
+        assert_eq!((err.column(), err.path()), (9, "/0"));
+        assert_eq!(
+            kind("[]", base.max_array_len(Some(0))),
+            Ok(JsonValue::Array(vec![]))
+        );
+        assert_eq!(
+            kind("[1]", base.max_array_len(Some(0))),
+            Err(JsonErrorKind::TooManyElements)
+        );
+
+        let opts = base.max_object_len(Some(1));
+        assert!(parse(r#"{"a": {"b": 1}}"#, opts).is_ok());
+        let err = parse(r#"{"a": {"b": 1, "c": 2}}"#, opts).unwrap_err();
+        assert_eq!(err.kind(), JsonErrorKind::TooManyMembers);
+        assert_eq!((err.column(), err.path()), (16, "/a"));
+    }
+}
+
+#[test]
+fn test_duplicate_keys() {
+    let src = r#"{"a": 1, "b": {"c": [], "c": true, "d": 0, "c": "x"}, "a": 2}"#;
+
+    for mode in &[ParseMode::Recursive, ParseMode::Iterative] {
+        let parse = |policy| {
+            let opts = ParseOptions::new().mode(*mode).duplicate_keys(policy);
+            json_rs::parse_with(src, &opts)
+        };
+
+        let val = json_rs::parse(src).unwrap();
+        assert_eq!(val, parse(DuplicateKeyPolicy::KeepLast).unwrap());
+        assert_eq!(val["a"], JsonValue::Number(2.into()));
+        assert_eq!(val["b"]["c"], JsonValue::String("x".to_owned()));
+        assert_eq!(val["b"].as_map().unwrap().len(), 2);
+
+        let val = parse(DuplicateKeyPolicy::KeepFirst).unwrap();
+        assert_eq!(val["a"], JsonValue::Number(1.into()));
+        assert_eq!(val["b"]["c"], JsonValue::Array(vec![]));
+
+        let val = parse(DuplicateKeyPolicy::CollectAll).unwrap();
+        assert_eq!(
+            val["a"],
+            JsonValue::Array(vec![
+                JsonValue::Number(1.into()),
+                JsonValue::Number(2.into())
+            ])
+        );
+        assert_eq!(
+            val["b"]["c"],
+            JsonValue::Array(vec![
+                JsonValue::Array(vec![]),
+                JsonValue::Boolean(true),
+                JsonValue::String("x".to_owned()),
+            ])
+        );
+        assert_eq!(val["b"]["d"], JsonValue::Number(0.into()));
+
+        let err = parse(DuplicateKeyPolicy::Reject).unwrap_err();
+        assert_eq!(err.kind(), JsonErrorKind::DuplicateKey);
+        assert_eq!(err.path(), "/b/c");
+        assert_eq!(err.column(), 25);
+        assert_eq!(
+            err.to_string(),
+            "duplicate object key at line 1 column 25 in /b/c"
+        );
+    }
+
+    let opts = ParseOptions::new()
+        .duplicate_keys(DuplicateKeyPolicy::KeepLast)
+        .max_object_len(Some(2));
+    let err = json_rs::parse_with(r#"{"a": 1, "a": 2, "a": 3}"#, &opts).unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::TooManyMembers);
+}
+
+#[test]
+fn test_object_order() {
+    let src = r#"{"z":1,"a":{"y":[],"b":null,"x":true},"m":"s"}"#;
+    for mode in &[ParseMode::Recursive, ParseMode::Iterative] {
+        let val = json_rs::parse_with(src, &ParseOptions::new().mode(*mode)).unwrap();
+        let keys: Vec<&str> = val.as_map().unwrap().keys().map(String::as_str).collect();
+        assert_eq!(keys, ["z", "a", "m"]);
+        assert_eq!(val.stringify(), src);
+    }
+
+    let mut val = json_rs::parse(src).unwrap();
+    assert_eq!(
+        format!("{:?}", val["a"]),
+        r#"Object({"y": Array([]), "b": Null, "x": Boolean(true)})"#
+    );
+
+    if let JsonValue::Object(ref mut map) = val {
+        map.insert("b".to_owned(), JsonValue::Null);
+        map.insert("z".to_owned(), JsonValue::Boolean(false));
+    }
+    assert_eq!(
+        val.stringify(),
+        r#"{"z":false,"a":{"y":[],"b":null,"x":true},"m":"s","b":null}"#
+    );
+
+    let val = json_rs::parse(r#"{"a":1,"b":2,"a":3}"#).unwrap();
+    assert_eq!(val.stringify(), r#"{"a":3,"b":2}"#);
+}
+
+#[test]
+fn test_parse_integer() {
+    let parse = |src| json_rs::parse(src).unwrap().as_num().cloned().unwrap();
+
+    let n = parse("9007199254740993");
+    assert_eq!(n.as_u64(), Some(9_007_199_254_740_993));
+    assert_eq!(n.as_i64(), Some(9_007_199_254_740_993));
+    assert_eq!(n.as_f64(), 9_007_199_254_740_992.0);
+    assert_eq!(n.to_string(), "9007199254740993");
+
+    let n = parse("18446744073709551615");
+    assert_eq!(n.as_u64(), Some(u64::MAX));
+    assert_eq!(n.as_i64(), None);
+    assert!(n.is_u64() && !n.is_i64());
+
+    let n = parse("-9223372036854775808");
+    assert_eq!(n.as_u64(), None);
+    assert_eq!(n.as_i64(), Some(i64::MIN));
+
+    // out of 64-bit range
+    let n = parse("18446744073709551616");
+    assert!(n.is_f64());
+    assert_eq!(n.as_f64(), 18_446_744_073_709_551_616.0);
+    assert!(parse("-9223372036854775809").is_f64());
+
+    let n = parse("-0");
+    assert!(n.is_f64());
+    assert!(n.as_f64().is_sign_negative());
+    assert_eq!(n.to_string(), "-0.0");
+
+    let n = parse("1.0");
+    assert!(n.is_f64());
+    assert_eq!(n.as_u64(), None);
+    assert_ne!(JsonValue::Number(n), JsonValue::Number(1.into()));
+    assert!(parse("1e2").is_f64());
+
+    // floats are written so that they read back as floats
+    for &(f, s) in &[
+        (1.0, "1.0"),
+        (1e300, "1e300"),
+        (-2.5e-10, "-2.5e-10"),
+        (0.1, "0.1"),
+    ] {
+        let n = Number::from(f);
+        assert_eq!(n.to_string(), s);
+        assert_eq!(parse(s), n);
+        assert!(parse(s).is_f64());
+    }
+
+    let src = r#"[0,1,-1,9007199254740993,18446744073709551615,-9223372036854775808,1.5]"#;
+    assert_eq!(json_rs::parse(src).unwrap().stringify(), src);
+    assert_eq!(
+        JsonValue::Array(vec![
+            JsonValue::Number(u64::MAX.into()),
+            JsonValue::Number((-2i8).into()),
+            JsonValue::Number(0.25.into()),
+        ])
+        .stringify(),
+        "[18446744073709551615,-2,0.25]"
+    );
+}
+
+#[test]
+fn test_arbitrary_precision() {
+    let opts = ParseOptions::new().arbitrary_precision(true);
+    let parse = |src| json_rs::parse_with(src, &opts);
+
+    let src = r#"{"amount":1.10,"big":1e400,"tiny":-1E-10000,"dec":123456789012345678901234567890.123,"id":18446744073709551616,"neg":-0}"#;
+    let val = parse(src).unwrap();
+    assert_eq!(val.stringify(), src);
+    assert_eq!(val["amount"].as_num().unwrap().as_literal(), Some("1.10"));
+    assert_eq!(val["amount"].as_num().unwrap().as_f64(), 1.1);
+    assert_eq!(val["big"].as_num().unwrap().as_f64(), f64::INFINITY);
+    assert_eq!(format!("{:?}", val["big"]), "Number(1e400)");
+    assert_eq!(val["id"].as_num().unwrap().as_u64(), None);
+    assert!(val["id"].as_num().unwrap().is_f64());
+    assert_eq!(
+        json_rs::parse("1e400").unwrap_err().kind(),
+        JsonErrorKind::NumberTooBig
+    );
+
+    let n = parse("-42").unwrap().as_num().cloned().unwrap();
+    assert_eq!(n.as_i64(), Some(-42));
+    assert_eq!(n.as_u64(), None);
+    assert!(n.is_i64() && !n.is_f64());
+
+    assert_eq!(
+        parse("01").unwrap_err().kind(),
+        JsonErrorKind::InvalidNumber
+    );
+    let opts = opts.max_number_len(Some(4));
+    assert_eq!(
+        json_rs::parse_with("12345", &opts).unwrap_err().kind(),
+        JsonErrorKind::NumberTooLong
+    );
+
+    let n = Number::from_literal("0.1000").unwrap();
+    assert_eq!(JsonValue::Number(n).stringify(), "0.1000");
+    assert!(Number::from_literal("1.").is_none());
+    assert!(Number::from_literal("+1").is_none());
+}
+
+#[test]
+fn test_lossy_numbers() {
+    let lossy = |src| {
+        let opts = ParseOptions::new().lossy_numbers(LossyNumberPolicy::Reject);
+        json_rs::parse_with(src, &opts).err().map(|e| e.kind())
+    };
+
+    assert_eq!(lossy("1e-10000"), Some(JsonErrorKind::NumberUnderflow));
+    assert_eq!(lossy("-0.1e-400"), Some(JsonErrorKind::NumberUnderflow));
+    assert_eq!(
+        lossy("18446744073709551617"),
+        Some(JsonErrorKind::IntegerPrecisionLoss)
+    );
+    assert_eq!(
+        lossy("123456789012345678901234567890.123"),
+        Some(JsonErrorKind::DecimalRounding)
+    );
+    assert_eq!(
+        lossy("0.10000000000000001"),
+        Some(JsonErrorKind::DecimalRounding)
+    );
+    assert_eq!(
+        lossy("4.9406564584124654e-324"),
+        Some(JsonErrorKind::DecimalRounding)
+    );
+    assert_eq!(
+        lossy("8.000000000000001"),
+        Some(JsonErrorKind::DecimalRounding)
+    );
+
+    for src in &[
+        "0",
+        "-0",
+        "0.0",
+        "0e-10000",
+        "0.1",
+        "1.10",
+        "-1.5E+10",
+        "1.0000000000000002",
+        "2.2250738585072014e-308",
+        "1.7976931348623157e+308",
+        "5e-324",
+        "9007199254740993",
+        "18446744073709551615",
+        "18446744073709551616",
+        "1e22",
+        "100e-2",
+    ] {
+        assert_eq!(lossy(src), None, "{}", src);
+    }
+
+    let src = r#"{"a": [1e-10000, 0.5, 1.00000000000000000001]}"#;
+    let opts = ParseOptions::new().lossy_numbers(LossyNumberPolicy::Warn);
+    for mode in &[ParseMode::Recursive, ParseMode::Iterative] {
+        let (val, warnings) = json_rs::parse_with_warnings(src, &opts.mode(*mode)).unwrap();
+        assert_eq!(val, json_rs::parse(src).unwrap());
+        let warnings: Vec<String> = warnings.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            warnings,
+            [
+                "number underflows to zero at line 1 column 8 in /a/0",
+                "number rounded to nearest float at line 1 column 23 in /a/2",
+            ]
+        );
+    }
+
+    let (_, warnings) = json_rs::parse_with_warnings(src, &ParseOptions::new()).unwrap();
+    assert!(warnings.is_empty());
+    let opts = opts.arbitrary_precision(true);
+    let (_, warnings) = json_rs::parse_with_warnings(src, &opts).unwrap();
+    assert!(warnings.is_empty());
+}
+
+// `tracing` takes precedence over `log` when both are enabled
+#[cfg(all(feature = "log", not(feature = "tracing")))]
+#[test]
+fn test_log_events() {
+    use std::sync::Mutex;
+
+    struct Logger(Mutex<Vec<String>>);
+
+    impl log::Log for Logger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.target() == "json_rs"
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                let line = format!("{} {}", record.level(), record.args());
+                self.0.lock().unwrap().push(line);
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: Logger = Logger(Mutex::new(Vec::new()));
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(log::LevelFilter::Trace);
+
+    assert!(json_rs::parse(r#"{"a": [1, x]}"#).is_err());
+    assert_eq!(
+        *LOGGER.0.lock().unwrap(),
+        [
+            r#"TRACE value_start kind="object" offset=0"#,
+            r#"TRACE value_start kind="array" offset=6"#,
+            r#"TRACE value_start kind="number" offset=7"#,
+            r#"TRACE value_end kind="number" offset=8"#,
+            r#"DEBUG error kind="invalid value" offset=10 line=1 column=11 path="/a/1""#,
+        ]
+    );
+
+    // offsets count from the start of the input, not of what is buffered
+    LOGGER.0.lock().unwrap().clear();
+    let reader = ChunkedReader {
+        src: b"[1, 2, x]",
+        chunk: 2,
+        fail_at: None,
+    };
+    assert!(json_rs::parse_reader(reader).is_err());
+    assert_eq!(
+        *LOGGER.0.lock().unwrap(),
+        [
+            r#"TRACE value_start kind="array" offset=0"#,
+            r#"TRACE value_start kind="number" offset=1"#,
+            r#"TRACE value_end kind="number" offset=2"#,
+            r#"TRACE value_start kind="number" offset=4"#,
+            r#"TRACE value_end kind="number" offset=5"#,
+            r#"DEBUG error kind="invalid value" offset=7 line=1 column=8 path="/2""#,
+        ]
+    );
+
+    // events trace the same values as parsing into a tree
+    let src = r#"{"a": [1, "b", {}], "c": tru"#;
+    let take_log = || std::mem::take(&mut *LOGGER.0.lock().unwrap());
+    take_log();
+    assert!(json_rs::parse(src).is_err());
+    let expected = take_log();
+    assert_eq!(
+        expected,
+        [
+            r#"TRACE value_start kind="object" offset=0"#,
+            r#"TRACE value_start kind="array" offset=6"#,
+            r#"TRACE value_start kind="number" offset=7"#,
+            r#"TRACE value_end kind="number" offset=8"#,
+            r#"TRACE value_start kind="string" offset=10"#,
+            r#"TRACE value_end kind="string" offset=13"#,
+            r#"TRACE value_start kind="object" offset=15"#,
+            r#"TRACE value_end kind="object" offset=17"#,
+            r#"TRACE value_end kind="array" offset=18"#,
+            r#"TRACE value_start kind="boolean" offset=25"#,
+            r#"DEBUG error kind="unexpected end of input" offset=28 line=1 column=29 path="/c""#,
+        ]
+    );
+    assert!(json_rs::events(src).any(|event| event.is_err()));
+    assert_eq!(take_log(), expected);
+    let mut reader = ChunkedReader {
+        src: src.as_bytes(),
+        chunk: 3,
+        fail_at: None,
+    };
+    let opts = ParseOptions::new();
+    assert!(json_rs::events_reader_with(&mut reader, &opts).any(|event| event.is_err()));
+    assert_eq!(take_log(), expected);
+    let mut parser = json_rs::push_parser();
+    for piece in src.as_bytes().chunks(3) {
+        parser.feed(piece).unwrap();
+    }
+    assert!(parser.finish().is_err());
+    assert_eq!(take_log(), expected);
+}
+
+/// Hands out the input a few bytes at a time, failing at `fail_at`.
+struct ChunkedReader<'a> {
+    src: &'a [u8],
+    chunk: usize,
+    fail_at: Option<usize>,
+}
+
+impl std::io::Read for ChunkedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut n = self.chunk.min(buf.len()).min(self.src.len());
+        if let Some(at) = self.fail_at {
+            if at == 0 {
+                return Err(std::io::Error::other("connection reset"));
+            }
+            n = n.min(at);
+            self.fail_at = Some(at - n);
+        }
+        buf[..n].copy_from_slice(&self.src[..n]);
+        self.src = &self.src[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn test_parse_reader() {
+    fn chunked(src: &str, chunk: usize) -> ChunkedReader<'_> {
+        ChunkedReader {
+            src: src.as_bytes(),
+            chunk,
+            fail_at: None,
+        }
+    }
+
+    let sources = [
+        r#"{"key": ["\u00e9t\u00e9 \ud83d\ude00", -12.5e-3, true, null], "": {}}"#,
+        "[\"caf\u{e9} \u{1F600}\u{10FFFF}\", \"\\\\\\\"\\/\\b\\f\\n\\r\\t\", 18446744073709551615]",
+        " \n\t\"a\u{e9}b\" \r\n",
+        "[1,\n  nul]",
+        "{\"\u{e9}\u{e9}\":1 x",
+        "[\"\u{1F600}a\\n\u{e9}\u{1}\"]",
+        "[\"\\ud800\"]",
+        r#"{"a": 1, "a": 2}"#,
+        "[1e400]",
+        "[1.2.3]",
+        "123456789",
+        "[] []",
+        "",
+    ];
+    let opts = ParseOptions::new().duplicate_keys(DuplicateKeyPolicy::Reject);
+    for src in &sources {
+        let expected = json_rs::parse_with(src, &opts);
+        for chunk in 1..8 {
+            for mode in &[ParseMode::Recursive, ParseMode::Iterative] {
+                let opts = opts.mode(*mode);
+                let res = json_rs::parse_reader_with(chunked(src, chunk), &opts);
+                assert_eq!(res, expected, "{:?} in chunks of {}", src, chunk);
+            }
+        }
+    }
+
+    let long = format!("[{}]", vec!["\"\u{e9}\u{1F600}\", 1.5"; 10_000].join(","));
+    let val = json_rs::parse_reader(chunked(&long, 4096)).unwrap();
+    assert_eq!(val, json_rs::parse(&long).unwrap());
+
+    // a char split across reads at the end of input
+    let err = json_rs::parse_reader(&b"\"\xF0\x9F\x98"[..]).unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::InvalidUtf8);
+    assert_eq!((err.offset(), err.column()), (1, 2));
+
+    let err = json_rs::parse_reader(&b"[\"ab\", \"c\xFFd\"]"[..]).unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::InvalidUtf8);
+    assert_eq!(err.to_string(), "invalid UTF-8 at line 1 column 10 in /1");
+
+    let reader = ChunkedReader {
+        src: b"[1, 2, 3]",
+        chunk: 2,
+        fail_at: Some(5),
+    };
+    let err = json_rs::parse_reader(reader).unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::Io);
+    assert_eq!(err.offset(), 5);
+    assert_eq!(err.io_error().unwrap().to_string(), "connection reset");
+    assert_eq!(
+        err.to_string(),
+        "i/o error: connection reset at line 1 column 6 in /1"
+    );
+    assert!(std::error::Error::source(&err).is_some());
+
+    let opts = ParseOptions::new().max_input_len(Some(8));
+    let err = json_rs::parse_reader_with(chunked("[1, 2, 3] ", 3), &opts).unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::InputTooLarge);
+    assert!(
+        json_rs::parse_reader_with(chunked("[1, 2, 3]", 3), &opts.max_input_len(Some(9))).is_ok()
+    );
+}
+
+/// Rebuilds a value from events, failing like `parse` would.
+fn collect_events(events: json_rs::JsonEvents) -> Result<JsonValue, json_rs::JsonError> {
+    let mut stack: Vec<(JsonValue, Option<String>)> = Vec::new();
+    let mut key = None;
+    let mut root = None;
+    for event in events {
+        let val = match event? {
+            JsonEvent::Key(k) => {
+                key = Some(k);
+                continue;
+            }
+            JsonEvent::StartArray => {
+                stack.push((JsonValue::Array(vec![]), key.take()));
+                continue;
+            }
+            JsonEvent::StartObject => {
+                stack.push((JsonValue::Object(JsonMap::new()), key.take()));
+                continue;
+            }
+            JsonEvent::EndArray | JsonEvent::EndObject => {
+                let (val, k) = stack.pop().unwrap();
+                key = k;
+                val
+            }
+            JsonEvent::Null => JsonValue::Null,
+            JsonEvent::Boolean(b) => JsonValue::Boolean(b),
+            JsonEvent::Number(n) => JsonValue::Number(n),
+            JsonEvent::String(s) => JsonValue::String(s),
+            JsonEvent::Wtf8String(bytes) => JsonValue::Wtf8String(bytes),
+        };
+        match stack.last_mut() {
+            Some((JsonValue::Array(arr), _)) => arr.push(val),
+            Some((JsonValue::Object(map), _)) => {
+                map.insert(key.take().unwrap(), val);
+            }
+            Some(_) => unreachable!(),
+            None => root = Some(val),
+        }
+    }
+    Ok(root.unwrap())
+}
+
+#[test]
+fn test_events() {
+    use JsonEvent::*;
+
+    let src = r#" {"a": [1, "x", {}, []], "b": {"c": null, "d": -0.5}, "e": true} "#;
+    let events: Result<Vec<_>, _> = json_rs::events(src).collect();
+    assert_eq!(
+        events.unwrap(),
+        [
+            StartObject,
+            Key("a".to_owned()),
+            StartArray,
+            Number(1.into()),
+            String("x".to_owned()),
+            StartObject,
+            EndObject,
+            StartArray,
+            EndArray,
+            EndArray,
+            Key("b".to_owned()),
+            StartObject,
+            Key("c".to_owned()),
+            Null,
+            Key("d".to_owned()),
+            Number((-0.5).into()),
+            EndObject,
+            Key("e".to_owned()),
+            Boolean(true),
+            EndObject,
+        ]
+    );
+
+    let sources = [
+        "null",
+        " [ ] ",
+        r#"[[1, [2, [3]]], {"a": {"b": {}}}, "\ud83d\ude00"]"#,
+        "[1,\n  nul]",
+        "{\"\u{e9}\u{e9}\":1 x",
+        "[1, 2,]",
+        r#"{"a": 1,}"#,
+        r#"{"a" 1}"#,
+        "[1 2]",
+        "[] []",
+        "[\"\\ud800\"]",
+        "[1e400]",
+        "[",
+        "",
+    ];
+    for src in &sources {
+        let expected = json_rs::parse(src);
+        assert_eq!(collect_events(json_rs::events(src)), expected, "{:?}", src);
+        for chunk in 1..4 {
+            let mut reader = ChunkedReader {
+                src: src.as_bytes(),
+                chunk,
+                fail_at: None,
+            };
+            let events = json_rs::events_reader_with(&mut reader, &ParseOptions::new());
+            assert_eq!(collect_events(events), expected, "{:?}", src);
+        }
+    }
+
+    // the iterator is fused after an error
+    let mut events = json_rs::events("[1, x]");
+    assert_eq!(events.next(), Some(Ok(StartArray)));
+    assert_eq!(events.next(), Some(Ok(Number(1.into()))));
+    let err = events.next().unwrap().unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::InvalidValue);
+    assert_eq!(err.path(), "/1");
+    assert_eq!(events.next(), None);
+
+    let opts = ParseOptions::new().max_depth(None);
+    let deep = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+    assert_eq!(json_rs::events_with(&deep, &opts).count(), 200_000);
+
+    let opts = ParseOptions::new()
+        .max_input_len(Some(3))
+        .max_object_len(Some(1))
+        .duplicate_keys(DuplicateKeyPolicy::Reject);
+    let mut events = json_rs::events_with("null", &opts);
+    let err = events.next().unwrap().unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::InputTooLarge);
+    assert_eq!(events.next(), None);
+    let err = json_rs::events_with(r#"{"a":1,"a":2}"#, &opts.max_input_len(None))
+        .find_map(Result::err)
+        .unwrap();
+    assert_eq!(err.kind(), JsonErrorKind::TooManyMembers);
+}
+
+/// Pushes `src` in pieces of `chunk` bytes, collecting events up to and
+/// including the first error. A failing `finish` returns only the error.
+fn push_events(
+    src: &[u8],
+    chunk: usize,
+    opts: &ParseOptions,
+) -> Vec<json_rs::JsonResult<JsonEvent>> {
+    let mut parser = json_rs::push_parser_with(opts);
+    let mut all = Vec::new();
+    for piece in src.chunks(chunk) {
+        match parser.feed(piece) {
+            Ok(events) => all.extend(events.into_iter().map(Ok)),
+            Err(err) => {
+                all.push(Err(err));
+                return all;
+            }
+        }
+    }
+    match parser.finish() {
+        Ok(events) => all.extend(events.into_iter().map(Ok)),
+        Err(err) => all.push(Err(err)),
+    }
+    all
+}
+
+/// Checks that pushing `src` in pieces of `chunk` bytes gives the events
+/// and the error of `JsonEvents`, except for the events completed by a
+/// failing `finish`, which returns only the error.
+fn check_push_events(src: &str, chunk: usize, opts: &ParseOptions) {
+    let mut expected: Vec<_> = json_rs::events_with(src, opts).collect();
+    let mut parser = json_rs::push_parser_with(opts);
+    let mut pushed = Vec::new();
+    let mut fed = true;
+    for piece in src.as_bytes().chunks(chunk) {
+        match parser.feed(piece) {
+            Ok(events) => pushed.extend(events.into_iter().map(Ok)),
+            Err(err) => {
+                pushed.push(Err(err));
+                fed = false;
+                break;
+            }
+        }
+    }
+    if fed {
+        match parser.finish() {
+            Ok(events) => pushed.extend(events.into_iter().map(Ok)),
+            Err(err) => {
+                let end = expected.len().saturating_sub(1);
+                expected.drain(pushed.len().min(end)..end);
+                pushed.push(Err(err));
+            }
+        }
+    }
+    assert_eq!(pushed, expected, "{:?} in pieces of {}", src, chunk);
+}
+
+#[test]
+fn test_push_parser() {
+    let sources = [
+        "null",
+        "true",
+        "false x",
+        "tru",
+        "nul1",
+        "123",
+        "-12.5e+3 ",
+        "-",
+        "1x",
+        "[1e400]",
+        "[1.5, -0, 2e-3, 18446744073709551616]",
+        r#"{"kéy": "vé", "a": [true, false, null], "b": {}}"#,
+        r#""aé😀\n\"\\\/\b\f\r\t""#,
+        r#"["\ud800A", "\ud800\n", "\udc00", "\ud800"]"#,
+        r#"["\ud800\x"]"#,
+        r#"["\u12g4"]"#,
+        "\"caf\u{e9} \u{1f600}\"",
+        "[\"a\nb\"]",
+        "[1,]",
+        r#"{"a":1,}"#,
+        r#"{"a" 1}"#,
+        r#"{"a": 1 "b": 2}"#,
+        r#"{1: 2}"#,
+        "[1 2]",
+        "[] []",
+        "  ",
+        "[[[]]",
+        "[1",
+        "[1, 2",
+    ];
+    let opts = [
+        ParseOptions::new(),
+        ParseOptions::new().lone_surrogates(SurrogatePolicy::Preserve),
+        ParseOptions::new()
+            .max_depth(Some(2))
+            .max_string_len(Some(3)),
+    ];
+    for opts in &opts {
+        for src in &sources {
+            for chunk in 1..=src.len().max(1) {
+                check_push_events(src, chunk, opts);
+            }
+        }
+    }
+
+    let mut parser = json_rs::push_parser();
+    assert_eq!(parser.feed(b"[\"ab").unwrap(), [JsonEvent::StartArray]);
+    assert_eq!(
+        parser.feed(b"c\", 1").unwrap(),
+        [JsonEvent::String("abc".to_owned())]
+    );
+    assert_eq!(parser.feed(b"2").unwrap(), []);
+    assert_eq!(
+        parser.feed(b"]").unwrap(),
+        [JsonEvent::Number(12.into()), JsonEvent::EndArray]
+    );
+    assert_eq!(parser.finish().unwrap(), []);
+
+    // input cut off by its end is an error, even after complete events
+    let mut parser = json_rs::push_parser();
+    assert_eq!(
+        parser.feed(b"[1, 2").unwrap(),
+        [JsonEvent::StartArray, JsonEvent::Number(1.into())]
+    );
+    let err = parser.finish().unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::UnexpectedEnd);
+    assert_eq!(err.offset(), 5);
+
+    // invalid UTF-8 is reported where the valid text ends
+    let events = push_events(b"[\"a\xff\"]", 1, &ParseOptions::new());
+    let err = events.last().unwrap().clone().unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::InvalidUtf8);
+    assert_eq!(err.offset(), 3);
+    let err = push_events(b"\"\xc3", 8, &ParseOptions::new())
+        .pop()
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::InvalidUtf8);
+
+    // the first error is returned again
+    let mut parser = json_rs::push_parser_with(&ParseOptions::new().max_input_len(Some(4)));
+    assert_eq!(parser.feed(b"[1,").unwrap().len(), 2);
+    let err = parser.feed(b" 2]").unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::InputTooLarge);
+    assert_eq!(parser.feed(b"").unwrap_err(), err);
+    assert_eq!(parser.finish().unwrap_err(), err);
+
+    // a long string fed a byte at a time is only scanned once
+    let src = format!("[\"{}\\u00e9\"]", "x".repeat(200_000));
+    let events = push_events(src.as_bytes(), 1, &ParseOptions::new());
+    assert_eq!(events.len(), 3);
+    assert_eq!(
+        events[1],
+        Ok(JsonEvent::String(format!("{}\u{e9}", "x".repeat(200_000))))
+    );
+}
+
+#[test]
+fn test_parse_borrowed() {
+    let src = r#"{"id": "plain", "n\u00e9": "caf\u00e9", "tags": ["x", 1.5, null]}"#;
+    for &mode in &[ParseMode::Recursive, ParseMode::Iterative] {
+        let opts = ParseOptions::new().mode(mode);
+        let val = json_rs::parse_borrowed_with(src, &opts).unwrap();
+        let map = val.as_map().unwrap();
+        let keys: Vec<_> = map.keys().collect();
+        assert!(matches!(keys[0], Cow::Borrowed("id")));
+        assert!(matches!(keys[1], Cow::Owned(k) if k == "n\u{e9}"));
+        assert!(matches!(
+            map["id"],
+            JsonValueRef::String(Cow::Borrowed("plain"))
+        ));
+        assert!(matches!(
+            map["n\u{e9}"],
+            JsonValueRef::String(Cow::Owned(_))
+        ));
+        assert_eq!(map["tags"].as_slice().unwrap()[0].as_str(), Some("x"));
+        assert_eq!(
+            map["tags"].as_slice().unwrap()[1].as_num(),
+            Some(&1.5.into())
+        );
+        assert_eq!(
+            format!("{:#?}", val),
+            format!("{:#?}", json_rs::parse(src).unwrap())
+        );
+        assert_eq!(val.into_owned(), json_rs::parse(src).unwrap());
+    }
+
+    let sources = [
+        "\"\"",
+        r#"["\ud800", "a\"b"]"#,
+        r#"{"a": 1, "a": {"b": [2]}, "a": 3}"#,
+        "[1, [2, [3, {}]], {\"x\": \"y\"}]",
+        "[1,]",
+        r#"{"a": 1 "b": 2}"#,
+        r#"{"\ud800": 1}"#,
+    ];
+    let opts = [
+        ParseOptions::new(),
+        ParseOptions::new()
+            .lone_surrogates(SurrogatePolicy::Preserve)
+            .duplicate_keys(DuplicateKeyPolicy::CollectAll),
+        ParseOptions::new().duplicate_keys(DuplicateKeyPolicy::Reject),
+    ];
+    for opts in &opts {
+        for src in &sources {
+            assert_eq!(
+                json_rs::parse_borrowed_with(src, opts).map(JsonValue::from),
+                json_rs::parse_with(src, opts),
+                "{:?}",
+                src
+            );
+        }
+    }
+
+    let opts = ParseOptions::new()
+        .mode(ParseMode::Iterative)
+        .max_depth(None);
+    let deep = format!("{}0{}", "[{\"a\":".repeat(100_000), "}]".repeat(100_000));
+    let val = json_rs::parse_borrowed_with(&deep, &opts).unwrap();
+    drop(json_rs::parse_borrowed_with(&deep, &opts).unwrap());
+    assert_eq!(val.into_owned(), json_rs::parse_with(&deep, &opts).unwrap());
+}
+
+/// Renders a document as a compact outline, marking strings copied out of
+/// the source with `*`.
+struct Outline(String);
+
+impl<'a> JsonBuilder<'a> for Outline {
+    type Output = String;
+
+    fn null(&mut self) {
+        self.0.push_str("n ");
+    }
+
+    fn boolean(&mut self, b: bool) {
+        self.0.push_str(if b { "t " } else { "f " });
+    }
+
+    fn number(&mut self, n: Number) {
+        self.0 += &format!("{} ", n);
+    }
+
+    fn string(&mut self, s: Cow<'a, str>) {
+        let mark = if let Cow::Owned(_) = s { "*" } else { "" };
+        self.0 += &format!("{}{} ", s, mark);
+    }
+
+    fn wtf8_string(&mut self, bytes: Vec<u8>) {
+        self.0 += &format!("{:?} ", bytes);
+    }
+
+    fn begin_array(&mut self) {
+        self.0.push_str("[ ");
+    }
+
+    fn end_array(&mut self) {
+        self.0.push_str("] ");
+    }
+
+    fn begin_object(&mut self) {
+        self.0.push_str("{ ");
+    }
+
+    fn key(&mut self, k: Cow<'a, str>) {
+        self.string(k);
+        self.0.push_str(": ");
+    }
+
+    fn end_object(&mut self) {
+        self.0.push_str("} ");
+    }
+
+    fn finish(self) -> String {
+        self.0.trim_end().to_string()
+    }
+}
+
+#[test]
+fn test_parse_into() {
+    let src = r#"{"a": [1, true, null], "b\n": {"c": "d\u0065"}, "e": {}, "f": []}"#;
+    for &mode in &[ParseMode::Recursive, ParseMode::Iterative] {
+        let opts = ParseOptions::new().mode(mode);
+        assert_eq!(
+            json_rs::parse_into_with(src, &opts, Outline(String::new())).unwrap(),
+            "{ a : [ 1 t n ] b\n* : { c : de* } e : { } f : [ ] }"
+        );
+        assert_eq!(
+            json_rs::parse_into_with(src, &opts, JsonValueBuilder::default()),
+            json_rs::parse_with(src, &opts)
+        );
+    }
+
+    let err = json_rs::parse_into("[1, 2", Outline(String::new())).unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::UnexpectedEnd);
+    let opts = ParseOptions::new().duplicate_keys(DuplicateKeyPolicy::Reject);
+    let err =
+        json_rs::parse_into_with(r#"{"a":1,"a":2}"#, &opts, Outline(String::new())).unwrap_err();
+    assert_eq!(err.kind(), JsonErrorKind::DuplicateKey);
+    assert_eq!(err.path(), "/a");
+    let err = json_rs::events_with(r#"{"a":1,"a":2}"#, &opts)
+        .find_map(Result::err)
+        .unwrap();
+    assert_eq!(err.kind(), JsonErrorKind::DuplicateKey);
+
+    let src = r#"{"a": 1, "a": 2}"#;
+    let builder = JsonValueBuilder::new(DuplicateKeyPolicy::KeepFirst);
+    let val = json_rs::parse_into(src, builder).unwrap();
+    assert_eq!(val["a"], JsonValue::Number(1.into()));
+}
+
+#[test]
+fn test_json5() {
+    let json5 = ParseOptions::new().dialect(Dialect::Json5);
+    let src = "// config\n{\n  unquoted: 'single \"quoted\"',\n  $id_1: 0x1F, neg: -0XA,\n  \
+               lead: .5, trail: 5., plus: +1, exp: 5.e1,\n  'str': \"it's\\\n  continued\",\n  \
+               esc: '\\x41\\v\\0\\q',\u{a0}ключ: 'tab\there',\n  arr: [1, 2, /* two */ ],\n  \
+               nested: {a: null,},\n}\n/* end */ ";
+    let expected = json_rs::parse(
+        r#"{"unquoted": "single \"quoted\"", "$id_1": 31, "neg": -10, "lead": 0.5,
+            "trail": 5.0, "plus": 1, "exp": 50.0, "str": "it's  continued",
+            "esc": "A\u000b\u0000q", "ключ": "tab\there", "arr": [1, 2],
+            "nested": {"a": null}}"#,
+    );
+    for &mode in &[ParseMode::Recursive, ParseMode::Iterative] {
+        assert_eq!(json_rs::parse_with(src, &json5.mode(mode)), expected);
+    }
+    for chunk in 1..=8 {
+        let reader = ChunkedReader {
+            src: src.as_bytes(),
+            chunk,
+            fail_at: None,
+        };
+        assert_eq!(json_rs::parse_reader_with(reader, &json5), expected);
+        check_push_events(src, chunk, &json5);
+    }
+    assert_eq!(collect_events(json_rs::events_with(src, &json5)), expected);
+    let val = json_rs::parse_borrowed_with(src, &json5).unwrap();
+    assert!(matches!(
+        val.as_map().unwrap().keys().next(),
+        Some(Cow::Borrowed("unquoted"))
+    ));
+
+    let num = |s: &str| {
+        json_rs::parse_with(s, &json5)
+            .unwrap()
+            .as_num()
+            .unwrap()
+            .as_f64()
+    };
+    assert_eq!(num("Infinity"), f64::INFINITY);
+    assert_eq!(num("+Infinity"), f64::INFINITY);
+    assert_eq!(num("-Infinity"), f64::NEG_INFINITY);
+    assert!(num("NaN").is_nan());
+    assert!(num("-NaN").is_nan());
+    let val = json_rs::parse_with("[Infinity, -Infinity, NaN, 1]", &json5).unwrap();
+    assert_eq!(val.stringify(), "[null,null,null,1]");
+    assert_eq!(JsonValue::Number(f64::NAN.into()).stringify(), "null");
+    assert_eq!(num("-0x8000000000000000"), i64::MIN as f64);
+    let opts = json5.arbitrary_precision(true);
+    let val = json_rs::parse_with("[1.10, 0x10, +2]", &opts).unwrap();
+    assert_eq!(val.stringify(), "[1.10,16,2]");
+
+    let err_kind = |s: &str| json_rs::parse_with(s, &json5).unwrap_err().kind();
+    assert_eq!(err_kind("/"), JsonErrorKind::UnexpectedEnd);
+    assert_eq!(err_kind("1 /x"), JsonErrorKind::InvalidComment);
+    assert_eq!(err_kind("[1] /* open"), JsonErrorKind::UnexpectedEnd);
+    assert_eq!(err_kind("[1,,]"), JsonErrorKind::InvalidValue);
+    assert_eq!(err_kind("[,]"), JsonErrorKind::InvalidValue);
+    assert_eq!(err_kind("{,}"), JsonErrorKind::ExpectedObjectKey);
+    assert_eq!(err_kind("{1a: 1}"), JsonErrorKind::ExpectedObjectKey);
+    assert_eq!(err_kind("{a b: 1}"), JsonErrorKind::MissingColon);
+    assert_eq!(err_kind("{\u{b2}: 1}"), JsonErrorKind::ExpectedObjectKey);
+    assert_eq!(err_kind("{\u{663}: 1}"), JsonErrorKind::ExpectedObjectKey);
+    assert_eq!(err_kind("{a\u{b2}: 1}"), JsonErrorKind::MissingColon);
+    assert_eq!(
+        err_kind("{\\u0032: 1}"),
+        JsonErrorKind::InvalidUnicodeEscape
+    );
+    assert_eq!(
+        err_kind("{a\\u0020b: 1}"),
+        JsonErrorKind::InvalidUnicodeEscape
+    );
+    assert_eq!(err_kind("{\\uD800: 1}"), JsonErrorKind::LoneSurrogate);
+    assert_eq!(err_kind("{a\\x62: 1}"), JsonErrorKind::InvalidEscape);
+    assert_eq!(err_kind("{\\: 1}"), JsonErrorKind::InvalidEscape);
+    assert_eq!(err_kind("'a\nb'"), JsonErrorKind::ControlCharacterInString);
+    assert_eq!(err_kind("'\\1'"), JsonErrorKind::InvalidEscape);
+    assert_eq!(err_kind("'\\01'"), JsonErrorKind::InvalidEscape);
+    assert_eq!(err_kind("'\\00'"), JsonErrorKind::InvalidEscape);
+    assert_eq!(err_kind("'\\x4g'"), JsonErrorKind::InvalidUnicodeEscape);
+    assert_eq!(err_kind("0x"), JsonErrorKind::InvalidNumber);
+    assert_eq!(err_kind("01"), JsonErrorKind::InvalidNumber);
+    assert_eq!(err_kind("Infinit"), JsonErrorKind::UnexpectedEnd);
+    assert_eq!(err_kind("-Infinityy"), JsonErrorKind::TrailingCharacters);
+    assert_eq!(err_kind("0x10000000000000000"), JsonErrorKind::NumberTooBig);
+    let opts = json5.max_object_len(Some(1));
+    assert!(json_rs::parse_with("{a: 1,}", &opts).is_ok());
+
+    // an NFD `café`, a connector punctuation and escaped chars
+    let keys = "{cafe\u{301}: 1, a\u{203f}b: 2, \\u0061b: 3, a\\u0062c: 4, \\uD835\\uDC00: 5}";
+    for src in &[
+        "/",
+        "1 /x",
+        "[1] /* open",
+        "[1 /**/, 2 // x\r\n]",
+        "{a: 1,}",
+        "{ключ\u{2028}: 1}",
+        keys,
+        "'a\\\r\nb'",
+        "'a\\\u{2028}b\\\u{e9}'",
+        "[-Infinity, +Infinity, Infinity,]",
+        "[-Inf]",
+        "['\\0', '\\0a', '\\01']",
+    ] {
+        for chunk in 1..=src.len() {
+            check_push_events(src, chunk, &json5);
+        }
+    }
+    let expected = json_rs::parse(
+        "{\"cafe\u{301}\": 1, \"a\u{203f}b\": 2, \"ab\": 3, \"abc\": 4, \"\u{1d400}\": 5}",
+    );
+    assert_eq!(json_rs::parse_with(keys, &json5), expected);
+    for chunk in 1..=8 {
+        let reader = ChunkedReader {
+            src: keys.as_bytes(),
+            chunk,
+            fail_at: None,
+        };
+        assert_eq!(json_rs::parse_reader_with(reader, &json5), expected);
+    }
+    assert_eq!(
+        json_rs::parse_with("'\\0a\\0'", &json5),
+        Ok(JsonValue::String("\0a\0".to_owned()))
+    );
+    assert_eq!(
+        json_rs::parse_with("'a\\\r\nb\\\u{2028}c\\\u{e9}'", &json5),
+        Ok(JsonValue::String("abc\u{e9}".to_owned()))
+    );
+
+    // none of it is JSON
+    for src in &[
+        "// c\n1",
+        "[1,]",
+        "{a: 1}",
+        "'a'",
+        "0x1",
+        ".5",
+        "+1",
+        "Infinity",
+        "\u{a0}1",
+        r#""\x41""#,
+    ] {
+        assert!(json_rs::parse(src).is_err(), "{:?}", src);
+    }
+}
+
+#[test]
+fn test_jsonc() {
+    let jsonc = ParseOptions::new().dialect(Dialect::Jsonc);
+    let src = "{\n  // compiler options\n  \"compilerOptions\": {\n    \"strict\": true, /* \
+               for now */\n    \"paths\": [\"a\", \"b\",],\n  },\n}\n// end";
+    let expected = json_rs::parse(r#"{"compilerOptions": {"strict": true, "paths": ["a", "b"]}}"#);
+    for &mode in &[ParseMode::Recursive, ParseMode::Iterative] {
+        assert_eq!(json_rs::parse_with(src, &jsonc.mode(mode)), expected);
+    }
+    for chunk in 1..=8 {
+        check_push_events(src, chunk, &jsonc);
+    }
+    assert_eq!(collect_events(json_rs::events_with(src, &jsonc)), expected);
+
+    let err_kind = |s: &str| json_rs::parse_with(s, &jsonc).unwrap_err().kind();
+    assert_eq!(err_kind("[1] /* open"), JsonErrorKind::UnexpectedEnd);
+    assert_eq!(err_kind("[1,,]"), JsonErrorKind::InvalidValue);
+    assert_eq!(err_kind("{a: 1}"), JsonErrorKind::ExpectedObjectKey);
+    assert_eq!(err_kind("{'a': 1}"), JsonErrorKind::ExpectedObjectKey);
+    assert_eq!(err_kind("['a']"), JsonErrorKind::InvalidValue);
+    assert_eq!(err_kind("[0x1]"), JsonErrorKind::TrailingCharacters);
+    assert_eq!(err_kind("[.5]"), JsonErrorKind::InvalidValue);
+    assert_eq!(err_kind("[+1]"), JsonErrorKind::InvalidValue);
+    assert_eq!(err_kind("[Infinity]"), JsonErrorKind::InvalidValue);
+    assert_eq!(err_kind("[\"\\x41\"]"), JsonErrorKind::InvalidEscape);
+    assert_eq!(err_kind("\u{a0}1"), JsonErrorKind::InvalidValue);
+    assert_eq!(err_kind("\"a\\\nb\""), JsonErrorKind::InvalidEscape);
+}